@@ -1,15 +1,26 @@
+use crate::http::post_form;
+use crate::io::wait_socket;
+use crate::pacing::{Pacer, Pacing};
+use crate::signal;
+use crate::timeout::{format_duration, Timeouts};
+use crate::transcript::{
+    file_position, major_speaker, FilePosition, SourceFile, Token, Transcript, Utterance,
+};
 use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
 use std::fmt::Display;
-use std::fs::File;
-use std::io::{BufReader, Read};
-use std::thread::sleep;
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use tungstenite::{
-    client::AutoStream, connect, error::Error as WebSocketError, stream::Stream as StreamSwitcher,
-    Message, WebSocket,
+    client::AutoStream, error::Error as WebSocketError, handshake::HandshakeError,
+    stream::Stream as StreamSwitcher, Message, WebSocket,
 };
 use url::Url;
 
+const ISSUE_SERVICE_AUTHORIZATION_URL: &str =
+    "https://acp-api.amivoice.com/issue_service_authorization";
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub enum OutputType {
     Nil,
@@ -17,19 +28,77 @@ pub enum OutputType {
     Trace,
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
-struct SCommandOption {
-    audio_format: String,
-    grammar_file_names: String,
-    authorization: String,
+pub fn issue_service_authorization(
+    service_id: &str,
+    service_password: &str,
+    expires_in_millis: Option<u64>,
+) -> Result<String, String> {
+    let url = Url::parse(ISSUE_SERVICE_AUTHORIZATION_URL).unwrap();
+    let expires_in_millis = expires_in_millis.map(|v| v.to_string());
+    let mut params = vec![("sid", service_id), ("spw", service_password)];
+    if let Some(epi) = &expires_in_millis {
+        params.push(("epi", epi));
+    }
+
+    let token = post_form(&url, &params)
+        .map_err(|e| format!("failed to issue service authorization: {}", e))?;
+    let token = token.trim();
+    if token.is_empty() {
+        return Err("failed to issue service authorization: empty response".to_string());
+    }
+    Ok(token.to_string())
+}
+
+pub fn endpoint_url(is_with_log: bool) -> Url {
+    if is_with_log {
+        Url::parse("wss://acp-api.amivoice.com/v1/").unwrap()
+    } else {
+        Url::parse("wss://acp-api.amivoice.com/v1/nolog/").unwrap()
+    }
+}
+
+pub fn validate_api_key(api_key: &str) -> Result<(), String> {
+    if api_key.is_empty() {
+        return Err("API KEY is empty".to_string());
+    }
+    if let Some(c) = api_key.chars().find(|c| !c.is_ascii_graphic()) {
+        return Err(format!("API KEY contains invalid character: {:?}", c));
+    }
+    if api_key.len() < 16 {
+        return Err(format!(
+            "API KEY is too short ({} characters): check that the whole key was copied",
+            api_key.len()
+        ));
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct SCommandOption {
+    pub audio_format: String,
+    pub grammar_file_names: String,
+    pub authorization: String,
+    #[serde(default)]
+    pub is_speaker_diarization: bool,
 }
 
 impl SCommandOption {
+    pub fn without_authorization(&self) -> SCommandOption {
+        SCommandOption {
+            authorization: String::new(),
+            ..self.clone()
+        }
+    }
+
     fn create_message(&self) -> String {
-        format!(
+        let mut message = format!(
             "s {} {} authorization={}",
             self.audio_format, self.grammar_file_names, self.authorization
-        )
+        );
+        if self.is_speaker_diarization {
+            message.push_str(" speakerDiarization=True");
+        }
+        message
     }
 }
 
@@ -55,12 +124,15 @@ struct AEventToken {
     confidence: f32,
     starttime: u64,
     endtime: u64,
+    #[serde(default)]
     spoken: String,
+    #[serde(default)]
+    label: Option<String>,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 struct AEventResult {
-    tokens: Vec<UEventToken>,
+    tokens: Vec<AEventToken>,
     confidence: f32,
     starttime: u64,
     endtime: u64,
@@ -93,10 +165,56 @@ enum PacketData {
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(try_from = "SavedPacket")]
 struct Packet {
     inserted_time: String,
     data: PacketData,
     raw: String,
+    // source file of an A event when several files are concatenated
+    #[serde(flatten, skip_serializing_if = "Option::is_none")]
+    position: Option<FilePosition>,
+}
+
+// a packet as saved in json and jsonl files. data is rebuilt from raw, because files written by
+// older versions have data in an older shape (e.g. A event tokens without times)
+#[derive(Deserialize)]
+struct SavedPacket {
+    inserted_time: String,
+    #[serde(default)]
+    data: serde_json::Value,
+    #[serde(default)]
+    raw: String,
+    #[serde(default, flatten)]
+    position: Option<FilePosition>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(untagged)]
+enum EventLine {
+    Packet(Packet),
+    Error {
+        inserted_time: String,
+        error: String,
+    },
+}
+
+// a line of jsonl output. events of a --split-channels session are labeled with their channel
+#[derive(Debug, Clone, Deserialize, Serialize)]
+struct EventRecord {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    channel: Option<String>,
+    #[serde(flatten)]
+    event: EventLine,
+}
+
+impl EventLine {
+    fn to_line(&self, channel: Option<&str>) -> Result<String, String> {
+        let record = EventRecord {
+            channel: channel.map(|label| label.to_string()),
+            event: self.clone(),
+        };
+        serde_json::to_string(&record).map_err(|e| format!("failed to serialize event: {}", e))
+    }
 }
 
 impl Display for PacketData {
@@ -130,12 +248,10 @@ trait MsgExt {
     fn get_packet(&self) -> Result<Option<Packet>, String>;
 }
 
-fn parse_command_error(packet_text: &String) -> Option<String> {
-    if packet_text.len() > 2 {
-        let message = &packet_text[2..];
-        Some(message.to_string())
-    } else {
-        None
+fn parse_command_error(packet_text: &str) -> Option<String> {
+    match packet_text.get(2..) {
+        Some(message) if !message.is_empty() => Some(message.to_string()),
+        _ => None,
     }
 }
 
@@ -178,31 +294,34 @@ impl DeserializePayload<AEventPayload> for Option<String> {
     }
 }
 
+fn parse_packet_data(txt: &str) -> Result<PacketData, String> {
+    let command = txt.chars().next();
+    let suffix = parse_command_error(txt);
+    let packet_data = match command {
+        Some('s') => PacketData::ReSCommand(suffix),
+        Some('p') => PacketData::RePCommand(suffix.ok_or("failed to get error message")?),
+        Some('e') => PacketData::ReECommand(suffix),
+        Some('S') => {
+            PacketData::ReSEvent(convert_suffix_to_u64(suffix, "failed to get start time")?)
+        }
+        Some('E') => PacketData::ReEEvent(convert_suffix_to_u64(suffix, "failed to get end time")?),
+        Some('C') => PacketData::ReCEvent,
+        Some('U') => PacketData::ReUEvent(suffix.deserialize_packet()?),
+        Some('A') => PacketData::ReAEvent(suffix.deserialize_packet()?),
+        Some('G') => PacketData::ReGEvent(suffix),
+        _ => PacketData::Others,
+    };
+    Ok(packet_data)
+}
+
 impl MsgExt for Message {
     fn get_packet(&self) -> Result<Option<Packet>, String> {
         if let Message::Text(txt) = self {
-            let command = &txt[..1];
-            let suffix = parse_command_error(txt);
-            let packet_data = match command {
-                "s" => PacketData::ReSCommand(suffix),
-                "p" => PacketData::RePCommand(suffix.ok_or("failed to get error message")?),
-                "e" => PacketData::ReECommand(suffix),
-                "S" => {
-                    PacketData::ReSEvent(convert_suffix_to_u64(suffix, "failed to get start time")?)
-                }
-                "E" => {
-                    PacketData::ReEEvent(convert_suffix_to_u64(suffix, "failed to get end time")?)
-                }
-                "C" => PacketData::ReCEvent,
-                "U" => PacketData::ReUEvent(suffix.deserialize_packet()?),
-                "A" => PacketData::ReAEvent(suffix.deserialize_packet()?),
-                "G" => PacketData::ReGEvent(suffix),
-                _ => PacketData::Others,
-            };
             let packet = Packet {
                 raw: txt.clone(),
                 inserted_time: get_timestamp()?,
-                data: packet_data,
+                data: parse_packet_data(txt)?,
+                position: None,
             };
             Ok(Some(packet))
         } else {
@@ -211,23 +330,473 @@ impl MsgExt for Message {
     }
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
-struct JsonOutput {
+impl Packet {
+    fn is_sent(data: &PacketData) -> bool {
+        matches!(
+            data,
+            PacketData::SeSCommand(_) | PacketData::SePCommand | PacketData::SeECommand
+        )
+    }
+
+    // received packets are parsed again from raw, sent ones keep their saved data
+    fn reparse(saved: SavedPacket) -> Result<Packet, String> {
+        let data = match serde_json::from_value::<PacketData>(saved.data) {
+            Ok(data) if Packet::is_sent(&data) || saved.raw.is_empty() => data,
+            Err(e) if saved.raw.is_empty() => {
+                return Err(format!("failed to restore packet: {}", e))
+            }
+            _ => parse_packet_data(&saved.raw)?,
+        };
+        Ok(Packet {
+            inserted_time: saved.inserted_time,
+            data,
+            raw: saved.raw,
+            position: saved.position,
+        })
+    }
+}
+
+impl TryFrom<SavedPacket> for Packet {
+    type Error = String;
+
+    fn try_from(saved: SavedPacket) -> Result<Self, Self::Error> {
+        Packet::reparse(saved)
+    }
+}
+
+impl From<&AEventToken> for Token {
+    fn from(token: &AEventToken) -> Self {
+        Token {
+            written: token.written.clone(),
+            spoken: token.spoken.clone(),
+            confidence: token.confidence,
+            starttime: token.starttime,
+            endtime: token.endtime,
+            speaker: token.label.clone(),
+        }
+    }
+}
+
+impl From<&AEventResult> for Utterance {
+    fn from(result: &AEventResult) -> Self {
+        let tokens: Vec<Token> = result.tokens.iter().map(Token::from).collect();
+        Utterance {
+            text: result.text.clone(),
+            confidence: result.confidence,
+            starttime: result.starttime,
+            endtime: result.endtime,
+            speaker: major_speaker(&tokens),
+            position: None,
+            tokens,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct JsonOutput {
+    #[serde(default)]
     option: SCommandOption,
     packets: Vec<Packet>,
     lines: Vec<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     error_message: Option<String>,
+    // position of the sent audio in the original file, added to the times of the results
+    #[serde(default, skip_serializing_if = "is_zero")]
+    time_offset: u64,
+    // results of per-channel sessions, merged by transcript()
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    channels: Vec<ChannelOutput>,
+    // audio files concatenated into the session
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    files: Vec<SourceFile>,
+    // the session was interrupted, results after that point are missing
+    #[serde(default, skip_serializing_if = "is_false")]
+    is_incomplete: bool,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ChannelOutput {
+    pub label: String,
+    pub output: JsonOutput,
+}
+
+fn is_zero(value: &u64) -> bool {
+    *value == 0
+}
+
+fn is_false(value: &bool) -> bool {
+    !*value
+}
+
+fn is_session_document(text: &str) -> bool {
+    match serde_json::from_str::<serde_json::Value>(text) {
+        Ok(serde_json::Value::Object(object)) => object.contains_key("packets"),
+        _ => false,
+    }
+}
+
+impl JsonOutput {
+    pub fn load(path: &str) -> Result<JsonOutput, String> {
+        let text =
+            std::fs::read_to_string(path).map_err(|e| format!("failed to read {}: {}", path, e))?;
+        let saved = match serde_json::from_str::<JsonOutput>(&text) {
+            Ok(saved) => saved,
+            // a saved session, not jsonl. its own error is the useful one
+            Err(e) if is_session_document(&text) => {
+                return Err(format!("failed to load {}: {}", path, e))
+            }
+            Err(_) => JsonOutput::from_jsonl(&text)
+                .map_err(|e| format!("failed to load {}: {}", path, e))?,
+        };
+
+        Ok(saved.with_lines())
+    }
+
+    // lines are rebuilt from the A events, as the client does while receiving them
+    fn with_lines(self) -> JsonOutput {
+        let mut output = JsonOutput {
+            option: self.option,
+            error_message: self.error_message,
+            time_offset: self.time_offset,
+            files: self.files,
+            is_incomplete: self.is_incomplete,
+            channels: self
+                .channels
+                .into_iter()
+                .map(|channel| ChannelOutput {
+                    label: channel.label,
+                    output: channel.output.with_lines(),
+                })
+                .collect(),
+            ..JsonOutput::default()
+        };
+        for packet in self.packets {
+            if let PacketData::ReAEvent(payload) = &packet.data {
+                output.lines.push(payload.text.clone());
+            }
+            output.packets.push(packet);
+        }
+        output
+    }
+
+    // a session that failed before receiving anything
+    pub fn from_error(message: String) -> JsonOutput {
+        JsonOutput {
+            error_message: Some(message),
+            ..JsonOutput::default()
+        }
+    }
+
+    pub fn from_channels(
+        option: SCommandOption,
+        channels: Vec<(String, Result<JsonOutput, String>)>,
+    ) -> JsonOutput {
+        let mut errors = Vec::new();
+        let channels: Vec<ChannelOutput> = channels
+            .into_iter()
+            .map(|(label, result)| {
+                let output = match result {
+                    Ok(output) => output.without_authorization(),
+                    Err(e) => JsonOutput::from_error(e),
+                };
+                if let Some(e) = &output.error_message {
+                    errors.push(format!("channel {}: {}", label, e));
+                }
+                ChannelOutput { label, output }
+            })
+            .collect();
+        let is_incomplete = channels.iter().any(|channel| channel.output.is_incomplete);
+        JsonOutput {
+            option,
+            is_incomplete,
+            error_message: if errors.is_empty() {
+                None
+            } else {
+                Some(errors.join("\n"))
+            },
+            channels,
+            ..JsonOutput::default()
+        }
+    }
+
+    fn from_jsonl(text: &str) -> Result<JsonOutput, String> {
+        let mut output = JsonOutput::default();
+        let mut channels: Vec<(String, JsonOutput)> = Vec::new();
+        for (index, line) in text.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let record: EventRecord = serde_json::from_str(line)
+                .map_err(|e| format!("failed to parse event at line {}: {}", index + 1, e))?;
+            let target = match record.channel {
+                Some(label) => match channels.iter().position(|(l, _)| *l == label) {
+                    Some(i) => &mut channels[i].1,
+                    None => {
+                        channels.push((label, JsonOutput::default()));
+                        &mut channels.last_mut().unwrap().1
+                    }
+                },
+                None => &mut output,
+            };
+            match record.event {
+                EventLine::Packet(packet) => target.packets.push(packet),
+                EventLine::Error { error, .. } => target.error_message = Some(error),
+            }
+        }
+        if channels.is_empty() {
+            return Ok(output);
+        }
+        let channels = channels
+            .into_iter()
+            .map(|(label, output)| (label, Ok(output)))
+            .collect();
+        Ok(JsonOutput::from_channels(
+            SCommandOption::default(),
+            channels,
+        ))
+    }
+
+    pub fn without_authorization(&self) -> JsonOutput {
+        JsonOutput {
+            option: self.option.without_authorization(),
+            channels: self
+                .channels
+                .iter()
+                .map(|channel| ChannelOutput {
+                    label: channel.label.clone(),
+                    output: channel.output.without_authorization(),
+                })
+                .collect(),
+            ..self.clone()
+        }
+    }
+
+    pub fn is_incomplete(&self) -> bool {
+        self.is_incomplete
+    }
+
+    pub fn is_succeeded(&self) -> bool {
+        self.error_message.is_none()
+    }
+
+    pub fn error_message(&self) -> Option<&String> {
+        self.error_message.as_ref()
+    }
+
+    pub fn option(&self) -> &SCommandOption {
+        &self.option
+    }
+
+    pub fn transcript(&self) -> Transcript {
+        if !self.channels.is_empty() {
+            return self.merged_transcript();
+        }
+        let utterances = self
+            .packets
+            .iter()
+            .filter_map(|packet| match &packet.data {
+                PacketData::ReAEvent(payload) => payload.results.first().map(|result| {
+                    // the text of the event, as in lines, unless an option rewrites it
+                    Utterance {
+                        text: payload.text.clone(),
+                        ..Utterance::from(result)
+                    }
+                }),
+                _ => None,
+            })
+            .map(|mut utterance| {
+                utterance.starttime += self.time_offset;
+                utterance.endtime += self.time_offset;
+                for token in utterance.tokens.iter_mut() {
+                    token.starttime += self.time_offset;
+                    token.endtime += self.time_offset;
+                }
+                utterance
+            })
+            .collect();
+        let mut transcript = Transcript {
+            utterances,
+            files: self.files.clone(),
+        };
+        transcript.locate_files();
+        transcript
+    }
+
+    // utterances of all channels in order of time, labeled with the channel as the speaker
+    fn merged_transcript(&self) -> Transcript {
+        let mut utterances: Vec<Utterance> = self
+            .channels
+            .iter()
+            .flat_map(|channel| {
+                let label = channel.label.clone();
+                channel
+                    .output
+                    .transcript()
+                    .utterances
+                    .into_iter()
+                    .map(move |mut utterance| {
+                        utterance.speaker = Some(label.clone());
+                        for token in utterance.tokens.iter_mut() {
+                            token.speaker = Some(label.clone());
+                        }
+                        utterance
+                    })
+            })
+            .collect();
+        utterances.sort_by_key(|utterance| utterance.starttime);
+        Transcript {
+            utterances,
+            ..Transcript::default()
+        }
+    }
+
+    pub fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string_pretty(self).map_err(|e| format!("failed to serialize result: {}", e))
+    }
+
+    pub fn to_jsonl(&self) -> Result<String, String> {
+        let mut lines = Vec::new();
+        if self.channels.is_empty() {
+            lines = self.event_lines(None)?;
+        }
+        // the error of every channel is in its own lines
+        for channel in &self.channels {
+            lines.extend(channel.output.event_lines(Some(&channel.label))?);
+        }
+        Ok(lines.iter().map(|line| format!("{}\n", line)).collect())
+    }
+
+    // jsonl lines of the received events and the error, labeled with the channel if given
+    pub fn event_lines(&self, channel: Option<&str>) -> Result<Vec<String>, String> {
+        let mut lines = Vec::new();
+        for packet in &self.packets {
+            lines.push(EventLine::Packet(packet.clone()).to_line(channel)?);
+        }
+        if let Some(msg) = &self.error_message {
+            let line = EventLine::Error {
+                inserted_time: get_timestamp()?,
+                error: msg.clone(),
+            };
+            lines.push(line.to_line(channel)?);
+        }
+        Ok(lines)
+    }
 }
 
 pub struct AmiWebSocketClient {
     output_data: JsonOutput,
-    audio_file_reader: BufReader<File>,
-    result_file_path: String,
+    audio_reader: Box<dyn Read>,
     output_type: OutputType,
     socket: WebSocket<AutoStream>,
     is_end_initialize: bool,
-    is_json_output: bool,
+    event_sinks: Vec<Box<dyn Write>>,
+    channel_label: Option<String>,
+    pacing: Pacing,
+    timeouts: Timeouts,
+    phase: Phase,
+    started: Instant,
+    phase_started: Instant,
+    last_activity: Instant,
+    interrupted_at: Option<Instant>,
+}
+
+// how long an interrupted session waits for the final results after sending e
+const INTERRUPT_GRACE: Duration = Duration::from_secs(5);
+// longest wait before a session notices an interrupt delivered to another thread
+const INTERRUPT_CHECK_INTERVAL: Duration = Duration::from_millis(200);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Phase {
+    Initialize,
+    SendAudio,
+    Finish,
+}
+
+impl Phase {
+    fn describe(&self) -> &'static str {
+        match self {
+            Phase::Initialize => "waiting for the response to s command",
+            Phase::SendAudio => "sending audio",
+            Phase::Finish => "waiting for the response to e command",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Timeout {
+    Initialize,
+    Idle,
+    Session,
+    Interrupt,
+}
+
+// tungstenite::connect cannot time out, so the TCP connection and the TLS and websocket handshakes
+// are done here, each bounded by the connect timeout
+fn connect_socket(url: &Url, timeout: Option<Duration>) -> Result<WebSocket<AutoStream>, String> {
+    let timed_out = || match timeout {
+        Some(timeout) => format!(
+            "timed out connecting to {} ({})",
+            url,
+            format_duration(timeout)
+        ),
+        None => format!("timed out connecting to {}", url),
+    };
+    let host = url
+        .host_str()
+        .ok_or("failed to connect by websocket: no host in the url")?;
+    let addrs = url
+        .socket_addrs(|| None)
+        .map_err(|e| format!("failed to connect by websocket: {}", e))?;
+    let mut result = Err(format!(
+        "failed to connect by websocket: {} is not found",
+        host
+    ));
+    for addr in addrs {
+        result = match timeout {
+            Some(timeout) => TcpStream::connect_timeout(&addr, timeout),
+            None => TcpStream::connect(addr),
+        }
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::TimedOut | std::io::ErrorKind::WouldBlock => timed_out(),
+            _ => format!("failed to connect by websocket: {}", e),
+        });
+        if result.is_ok() {
+            break;
+        }
+    }
+    let stream = result?;
+    stream
+        .set_read_timeout(timeout)
+        .and_then(|_| stream.set_write_timeout(timeout))
+        .and_then(|_| stream.set_nodelay(true))
+        .map_err(|e| format!("failed to configure socket: {}", e))?;
+
+    let stream = if url.scheme() == "wss" {
+        let connector = native_tls::TlsConnector::new()
+            .map_err(|e| format!("failed to connect by websocket: {}", e))?;
+        match connector.connect(host, stream) {
+            Ok(stream) => StreamSwitcher::Tls(stream),
+            Err(native_tls::HandshakeError::WouldBlock(_)) => return Err(timed_out()),
+            Err(native_tls::HandshakeError::Failure(e)) => {
+                return Err(format!("failed to connect by websocket: {}", e))
+            }
+        }
+    } else {
+        StreamSwitcher::Plain(stream)
+    };
+    match tungstenite::client(url.clone(), stream) {
+        Ok((socket, _)) => Ok(socket),
+        Err(HandshakeError::Interrupted(_)) => Err(timed_out()),
+        Err(HandshakeError::Failure(WebSocketError::Io(e)))
+            if matches!(
+                e.kind(),
+                std::io::ErrorKind::TimedOut | std::io::ErrorKind::WouldBlock
+            ) =>
+        {
+            Err(timed_out())
+        }
+        Err(HandshakeError::Failure(e)) => Err(format!("failed to connect by websocket: {}", e)),
+    }
 }
 
 trait SendMessageExt<T> {
@@ -240,12 +809,13 @@ impl SendMessageExt<Vec<u8>> for AmiWebSocketClient {
             raw: "p<audio data>".to_string(),
             inserted_time: get_timestamp()?,
             data: PacketData::SePCommand,
+            position: None,
         };
 
         match self.output_type {
             OutputType::Nil => (),
-            OutputType::Verbose => println!("{}", packet),
-            OutputType::Trace => println!("{:?}", packet),
+            OutputType::Verbose => eprintln!("{}", packet),
+            OutputType::Trace => eprintln!("{:?}", packet),
         }
 
         let message = Message::Binary(data);
@@ -253,10 +823,14 @@ impl SendMessageExt<Vec<u8>> for AmiWebSocketClient {
         if self.get_packets()? {
             return Ok(true);
         }
-        self.socket
-            .write_message(message)
-            .map_err(|e| format!("failed to send message: {}", e))?;
-        Ok(false)
+        match self.socket.write_message(message) {
+            Ok(()) => Ok(false),
+            // the message is queued, the server is not keeping up
+            Err(WebSocketError::Io(e)) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                self.flush_pending()
+            }
+            Err(e) => Err(format!("failed to send message: {}", e)),
+        }
     }
 }
 
@@ -266,44 +840,33 @@ impl SendMessageExt<Packet> for AmiWebSocketClient {
 
         match self.output_type {
             OutputType::Nil => (),
-            OutputType::Verbose => println!("{}", data),
-            OutputType::Trace => println!("{:?}", data),
+            OutputType::Verbose => eprintln!("{}", data),
+            OutputType::Trace => eprintln!("{:?}", data),
         }
 
         if self.get_packets()? {
             return Ok(true);
         }
-        self.socket
-            .write_message(message)
-            .map_err(|e| format!("failed to send message: {}", e))?;
-        Ok(false)
+        match self.socket.write_message(message) {
+            Ok(()) => Ok(false),
+            Err(WebSocketError::Io(e)) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                self.flush_pending()
+            }
+            Err(e) => Err(format!("failed to send message: {}", e)),
+        }
     }
 }
 
 impl AmiWebSocketClient {
     pub fn new(
-        api_key: String,
-        audio_format: String,
-        grammar_file_names: String,
+        option: SCommandOption,
         is_with_log: bool,
-        is_json_output: bool,
-        audio_file_path: String,
-        result_file_path: &str,
+        audio_reader: Box<dyn Read>,
         output_type: OutputType,
+        timeouts: Timeouts,
     ) -> Result<AmiWebSocketClient, String> {
-        std::fs::write(&result_file_path, "")
-            .map_err(|e| format!("failed to write result file (empty write for check): {}", e))?;
-
-        let reader = File::open(audio_file_path)
-            .map(|f| BufReader::new(f))
-            .map_err(|e| format!("failed to open file: {}", e))?;
-        let url = if is_with_log {
-            Url::parse("wss://acp-api.amivoice.com/v1/").unwrap()
-        } else {
-            Url::parse("wss://acp-api.amivoice.com/v1/nolog/").unwrap()
-        };
-        let (mut socket, _) =
-            connect(url).map_err(|e| format!("failed to connect by websocket: {}", e))?;
+        let url = endpoint_url(is_with_log);
+        let mut socket = connect_socket(&url, timeouts.connect)?;
         let stream = socket.get_mut();
         let stream = match stream {
             StreamSwitcher::Plain(s) => s,
@@ -315,97 +878,266 @@ impl AmiWebSocketClient {
 
         Ok(AmiWebSocketClient {
             output_data: JsonOutput {
-                option: SCommandOption {
-                    audio_format: audio_format.clone(),
-                    grammar_file_names: grammar_file_names.clone(),
-                    authorization: api_key.clone(),
-                },
+                option,
                 packets: Vec::new(),
                 lines: Vec::new(),
                 error_message: None,
+                time_offset: 0,
+                channels: Vec::new(),
+                files: Vec::new(),
+                is_incomplete: false,
             },
-            audio_file_reader: reader,
+            audio_reader,
             output_type: output_type.clone(),
-            result_file_path: result_file_path.to_string(),
-            socket: socket,
+            socket,
             is_end_initialize: false,
-            is_json_output: is_json_output,
+            event_sinks: Vec::new(),
+            channel_label: None,
+            pacing: Pacing::default(),
+            timeouts,
+            phase: Phase::Initialize,
+            started: Instant::now(),
+            phase_started: Instant::now(),
+            last_activity: Instant::now(),
+            interrupted_at: None,
         })
     }
 
     pub fn exec(&mut self) -> Result<(), String> {
-        let result = self.exec_in_socket();
+        let _signals = signal::install()?;
+        let mut result = self.exec_in_socket();
+        if self.interrupted_at.is_some() {
+            self.output_data.is_incomplete = true;
+            result = result.and(Err("interrupted: the results are incomplete".to_string()));
+        }
         if let Err(msg) = &result {
             self.output_data.error_message = Some(msg.clone());
             eprintln!("{}", msg);
+            let line = EventLine::Error {
+                inserted_time: get_timestamp()?,
+                error: msg.clone(),
+            };
+            self.emit_event(&line)?;
         }
 
-        let text = if self.is_json_output {
-            serde_json::to_string_pretty(&self.output_data)
-                .map_err(|e| format!("failed to serialize result: {}", e))?
-        } else {
-            self.output_data.lines.join("\n")
-        };
+        // an error of the session is more useful than one from closing after it
+        let closed = self
+            .socket
+            .close(None)
+            .map_err(|e| format!("failed to close websocket: {}", e));
+        result?;
+        closed
+    }
 
-        std::fs::write(&self.result_file_path, text)
-            .map_err(|e| format!("failed to write result file: {}", e))?;
+    pub fn set_time_offset(&mut self, millis: u64) {
+        self.output_data.time_offset = millis;
+    }
 
-        self.socket
-            .close(None)
-            .map_err(|e| format!("failed to close websocket: {}", e))?;
+    pub fn set_source_files(&mut self, files: Vec<SourceFile>) {
+        self.output_data.files = files;
+    }
+
+    pub fn set_pacing(&mut self, pacing: Pacing) {
+        self.pacing = pacing;
+    }
+
+    pub fn output(&self) -> &JsonOutput {
+        &self.output_data
+    }
 
-        result
+    pub fn add_event_sink(&mut self, sink: Box<dyn Write>) {
+        self.event_sinks.push(sink);
+    }
+
+    // labels the events written to the event sinks
+    pub fn set_channel_label(&mut self, label: &str) {
+        self.channel_label = Some(label.to_string());
+    }
+
+    fn emit_event(&mut self, event: &EventLine) -> Result<(), String> {
+        if self.event_sinks.is_empty() {
+            return Ok(());
+        }
+        // a line is written at once, sinks may be shared by the sessions of several channels
+        let line = format!("{}\n", event.to_line(self.channel_label.as_deref())?);
+        for sink in self.event_sinks.iter_mut() {
+            sink.write_all(line.as_bytes())
+                .and_then(|_| sink.flush())
+                .map_err(|e| format!("failed to write event: {}", e))?;
+        }
+        Ok(())
     }
 
     fn exec_in_socket(&mut self) -> Result<(), String> {
+        self.started = Instant::now();
+        self.enter(Phase::Initialize);
         self.start()?;
         while !self.is_end_initialize {
-            sleep(Duration::from_millis(100));
+            self.wait_socket(false, None)?;
             if self.get_packets()? {
                 return Ok(());
             }
+            if self.poll_interrupt() {
+                return Err("interrupted before the session started".to_string());
+            }
         }
+        self.enter(Phase::SendAudio);
         if self.send_audio()? {
             return Ok(());
         }
+        self.enter(Phase::Finish);
 
         loop {
             if self.get_packets()? {
                 break;
             }
-            sleep(Duration::from_micros(100));
+            self.wait_socket(false, None)?;
         }
 
         Ok(())
     }
 
+    fn poll_interrupt(&mut self) -> bool {
+        if self.interrupted_at.is_none() && signal::is_interrupted() {
+            self.interrupted_at = Some(Instant::now());
+            eprintln!(
+                "interrupted: finishing the session with the results so far (interrupt again to abort)"
+            );
+        }
+        self.interrupted_at.is_some()
+    }
+
+    fn enter(&mut self, phase: Phase) {
+        self.phase = phase;
+        self.phase_started = Instant::now();
+        self.last_activity = Instant::now();
+    }
+
+    // the earliest timeout that applies to the current phase.
+    // a timeout too long to be represented as an Instant never expires
+    fn deadline(&self) -> Option<(Instant, Timeout)> {
+        let after = |start: Instant, timeout: Option<Duration>, kind: Timeout| {
+            timeout
+                .and_then(|timeout| start.checked_add(timeout))
+                .map(|at| (at, kind))
+        };
+        let phase = match self.phase {
+            Phase::Initialize => after(
+                self.phase_started,
+                self.timeouts.initialize,
+                Timeout::Initialize,
+            ),
+            Phase::SendAudio | Phase::Finish => {
+                after(self.last_activity, self.timeouts.idle, Timeout::Idle)
+            }
+        };
+        let session = after(self.started, self.timeouts.session, Timeout::Session);
+        let interrupt = self
+            .interrupted_at
+            .and_then(|at| after(at, Some(INTERRUPT_GRACE), Timeout::Interrupt));
+        vec![phase, session, interrupt]
+            .into_iter()
+            .flatten()
+            .min_by_key(|(at, _)| *at)
+    }
+
+    fn check_deadline(&self) -> Result<(), String> {
+        let timeout = match self.deadline() {
+            Some((at, timeout)) if at <= Instant::now() => timeout,
+            _ => return Ok(()),
+        };
+        let describe = |timeout: Option<Duration>| timeout.map(format_duration).unwrap_or_default();
+        Err(match timeout {
+            Timeout::Initialize => format!(
+                "timed out: no response to s command within {}",
+                describe(self.timeouts.initialize)
+            ),
+            Timeout::Idle => format!(
+                "timed out: nothing from the server for {} while {}",
+                describe(self.timeouts.idle),
+                self.phase.describe()
+            ),
+            Timeout::Session => format!(
+                "timed out: the session did not finish within {} (stopped while {})",
+                describe(self.timeouts.session),
+                self.phase.describe()
+            ),
+            Timeout::Interrupt => format!(
+                "interrupted: gave up {} after {}",
+                self.phase.describe(),
+                format_duration(INTERRUPT_GRACE)
+            ),
+        })
+    }
+
+    // get_packets reads until the socket would block (including data buffered by TLS),
+    // so nothing is left unread while waiting here
+    fn wait_socket(&mut self, is_write: bool, timeout: Option<Duration>) -> Result<(), String> {
+        let stream = match self.socket.get_ref() {
+            StreamSwitcher::Plain(s) => s,
+            StreamSwitcher::Tls(s) => s.get_ref(),
+        };
+        let timeout = match self.deadline() {
+            Some((at, _)) => {
+                let left = at.saturating_duration_since(Instant::now());
+                Some(timeout.map_or(left, |timeout| timeout.min(left)))
+            }
+            None => timeout,
+        };
+        // a signal wakes up only the thread it is delivered to, so concurrent sessions check regularly
+        let timeout = match self.interrupted_at {
+            None => Some(timeout.map_or(INTERRUPT_CHECK_INTERVAL, |timeout| {
+                timeout.min(INTERRUPT_CHECK_INTERVAL)
+            })),
+            Some(_) => timeout,
+        };
+        wait_socket(stream, is_write, timeout)?;
+        self.poll_interrupt();
+        self.check_deadline()
+    }
+
     fn start(&mut self) -> Result<(), String> {
         let packet = Packet {
             raw: self.output_data.option.create_message(),
             inserted_time: get_timestamp()?,
             data: PacketData::SeSCommand(self.output_data.option.clone()),
+            position: None,
         };
         self.send_message(packet).map(|_| ())
     }
 
     fn send_audio(&mut self) -> Result<bool, String> {
-        let mut buf: [u8; 4096] = [0; 4096];
+        let mut buf = vec![0; self.pacing.chunk_size];
+        let mut pacer = Pacer::new(self.pacing.clone());
 
-        loop {
+        // an interruption stops the audio, the session is finished by e as usual
+        while !self.poll_interrupt() {
             let index = self
-                .audio_file_reader
+                .audio_reader
                 .read(&mut buf)
                 .map_err(|e| format!("failed to read audio: {}", e))?;
 
             if index == 0 {
                 break;
             }
+            // keep receiving results while waiting for the next chunk
+            while let Some(wait) = pacer.wait() {
+                if self.get_packets()? {
+                    return Ok(true);
+                }
+                self.wait_socket(false, Some(wait))?;
+            }
+            if self.poll_interrupt() {
+                break;
+            }
             let mut binary = buf[..index].to_vec();
             binary.insert(0, 112);
-            self.send_message(binary)?;
-
-            // I do not know well. but need
-            sleep(Duration::from_millis(5));
+            if self.send_message(binary)? {
+                return Ok(true);
+            }
+            pacer.sent(index);
+            self.last_activity = Instant::now();
+            self.check_deadline()?;
         }
 
         if self.get_packets()? {
@@ -415,15 +1147,38 @@ impl AmiWebSocketClient {
             raw: "e".to_string(),
             inserted_time: get_timestamp()?,
             data: PacketData::SeECommand,
+            position: None,
         };
         self.send_message(packet)?;
 
         Ok(false)
     }
 
+    // waits until the queued messages are written, that is until the server has taken the previous data
+    fn flush_pending(&mut self) -> Result<bool, String> {
+        loop {
+            if self.get_packets()? {
+                return Ok(true);
+            }
+            match self.socket.write_pending() {
+                Ok(()) => return Ok(false),
+                Err(WebSocketError::Io(e)) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                    self.wait_socket(true, None)?;
+                }
+                Err(e) => return Err(format!("failed to send message: {}", e)),
+            }
+        }
+    }
+
     fn get_packets(&mut self) -> Result<bool, String> {
-        while self.socket.can_read() {
+        loop {
+            // no more messages come once the server closed the connection,
+            // and waiting for them would return at once without end
+            if !self.socket.can_read() {
+                return Err(self.closed_error());
+            }
             let msg = match self.socket.read_message() {
+                Ok(Message::Close(_)) => return Err(self.closed_error()),
                 Ok(msg) => msg,
                 Err(e) => {
                     if let WebSocketError::Io(e) = e {
@@ -431,24 +1186,34 @@ impl AmiWebSocketClient {
                             std::io::ErrorKind::WouldBlock => break,
                             _ => return Err(format!("failed to read message: {}", e)),
                         }
+                    } else if !self.socket.can_read() {
+                        // closed, or the stream ended without a close frame
+                        return Err(self.closed_error());
                     } else {
                         return Err(format!("failed to read message: {}", e));
                     }
                 }
             };
-            let packet = if let Some(packet) = msg.get_packet()? {
+            let mut packet = if let Some(packet) = msg.get_packet()? {
                 packet
             } else {
                 continue;
             };
+            if let PacketData::ReAEvent(payload) = &packet.data {
+                packet.position = payload.results.first().and_then(|result| {
+                    let starttime =
+                        Utterance::from(result).starttime + self.output_data.time_offset;
+                    file_position(&self.output_data.files, starttime)
+                });
+            }
             match self.output_type {
                 OutputType::Nil => (),
-                OutputType::Verbose => println!("{}", packet),
-                OutputType::Trace => println!("{:?}", packet),
-            }
-            if self.is_json_output {
-                self.output_data.packets.push(packet.clone());
+                OutputType::Verbose => eprintln!("{}", packet),
+                OutputType::Trace => eprintln!("{:?}", packet),
             }
+            self.emit_event(&EventLine::Packet(packet.clone()))?;
+            self.output_data.packets.push(packet.clone());
+            self.last_activity = Instant::now();
             match packet.data {
                 PacketData::ReSCommand(msg) => {
                     self.is_end_initialize = true;
@@ -472,4 +1237,55 @@ impl AmiWebSocketClient {
         }
         Ok(false)
     }
+
+    fn closed_error(&self) -> String {
+        format!(
+            "server closed the connection while {}",
+            self.phase.describe()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn jsonl_keeps_the_channels_of_events() {
+        let text = concat!(
+            r#"{"channel":"a","inserted_time":"1","data":{"ReSCommand":null},"raw":"s"}"#,
+            "\n",
+            r#"{"channel":"b","inserted_time":"2","data":{"ReSCommand":null},"raw":"s"}"#,
+            "\n",
+            r#"{"channel":"b","inserted_time":"3","error":"failed"}"#,
+            "\n",
+        );
+        let output = JsonOutput::from_jsonl(text).unwrap();
+        let labels: Vec<&str> = output.channels.iter().map(|c| c.label.as_str()).collect();
+        assert_eq!(labels, vec!["a", "b"]);
+        assert_eq!(output.channels[0].output.packets.len(), 1);
+        assert_eq!(
+            output.error_message().map(|e| e.as_str()),
+            Some("channel b: failed")
+        );
+
+        let jsonl = output.to_jsonl().unwrap();
+        let lines: Vec<&str> = jsonl.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(&lines[..2], &text.lines().collect::<Vec<&str>>()[..2]);
+        assert!(lines[2].starts_with(r#"{"channel":"b","#));
+        assert!(lines[2].ends_with(r#""error":"failed"}"#));
+
+        // events of a single session are not labeled
+        let output = JsonOutput::from_jsonl(
+            text.lines()
+                .next()
+                .unwrap()
+                .replace(r#""channel":"a","#, "")
+                .as_str(),
+        )
+        .unwrap();
+        assert!(output.channels.is_empty());
+        assert!(!output.to_jsonl().unwrap().contains("channel"));
+    }
 }
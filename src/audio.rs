@@ -0,0 +1,757 @@
+use crate::transcript::SourceFile;
+use std::fs::File;
+use std::io::{BufReader, Cursor, Read, Seek, SeekFrom};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PcmFormat {
+    pub sample_rate: u32,
+    // bytes per sample frame (all channels)
+    pub block_align: u32,
+}
+
+impl PcmFormat {
+    // None when the offset does not fit in u64
+    pub fn byte_offset(&self, millis: u64) -> Option<u64> {
+        millis
+            .checked_mul(self.sample_rate as u64)
+            .map(|samples| samples / 1000)?
+            .checked_mul(self.block_align as u64)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct WavHeader {
+    pub format: PcmFormat,
+    pub format_tag: u16,
+    pub channels: u16,
+    pub bits_per_sample: u16,
+    // every byte before the samples of the data chunk
+    pub bytes: Vec<u8>,
+    pub data_size: u64,
+}
+
+impl WavHeader {
+    // header bytes with the RIFF and data chunk sizes set for data_size bytes of samples
+    pub fn with_data_size(&self, data_size: u64) -> Vec<u8> {
+        let mut bytes = self.bytes.clone();
+        let riff_size = (bytes.len() as u64 - 8 + data_size).min(u32::MAX as u64) as u32;
+        let data_size = data_size.min(u32::MAX as u64) as u32;
+        let at = bytes.len() - 4;
+        bytes[4..8].copy_from_slice(&riff_size.to_le_bytes());
+        bytes[at..].copy_from_slice(&data_size.to_le_bytes());
+        bytes
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TimeRange {
+    pub start: u64,
+    pub end: Option<u64>,
+}
+
+impl TimeRange {
+    pub fn parse(
+        start: Option<&str>,
+        end: Option<&str>,
+        duration: Option<&str>,
+    ) -> Result<TimeRange, String> {
+        let start = match start {
+            Some(s) => parse_time(s).map_err(|e| format!("failed to parse --start: {}", e))?,
+            None => 0,
+        };
+        let end = match (end, duration) {
+            (Some(end), _) => {
+                Some(parse_time(end).map_err(|e| format!("failed to parse --end: {}", e))?)
+            }
+            (None, Some(duration)) => Some(
+                parse_time(duration)
+                    .map_err(|e| format!("failed to parse --duration: {}", e))?
+                    .checked_add(start)
+                    .ok_or("--start and --duration are too large")?,
+            ),
+            (None, None) => None,
+        };
+        if matches!(end, Some(end) if end <= start) {
+            return Err("the end of the range must be after --start".to_string());
+        }
+        Ok(TimeRange { start, end })
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.start == 0 && self.end.is_none()
+    }
+
+    // empty for the whole audio
+    pub fn cache_key(&self) -> String {
+        match self.end {
+            _ if self.is_full() => String::new(),
+            Some(end) => format!("{}-{}", self.start, end),
+            None => format!("{}-", self.start),
+        }
+    }
+}
+
+// a year, far beyond any audio or timeout
+const MAX_TIME_MILLIS: u64 = 365 * 24 * 60 * 60 * 1000;
+
+// seconds ("90", "90.5") or clock time ("1:30", "01:02:03.5") to milliseconds
+pub fn parse_time(s: &str) -> Result<u64, String> {
+    let mut seconds = 0.0;
+    for part in s.trim().split(':') {
+        let value: f64 = part
+            .parse()
+            .ok()
+            .filter(|value: &f64| value.is_finite())
+            .ok_or_else(|| format!("invalid time \"{}\" (e.g. 90, 1:30, 01:02:03.5)", s))?;
+        if value < 0.0 {
+            return Err(format!("invalid time \"{}\": must not be negative", s));
+        }
+        seconds = seconds * 60.0 + value;
+    }
+    let millis = (seconds * 1000.0).round();
+    if millis > MAX_TIME_MILLIS as f64 {
+        return Err(format!(
+            "invalid time \"{}\": must not be longer than {} hours",
+            s,
+            MAX_TIME_MILLIS / 3_600_000
+        ));
+    }
+    Ok(millis as u64)
+}
+
+// headerless PCM formats of AmiVoice Cloud Platform (8k and 16k are 16 bit little endian)
+pub fn pcm_format_of(audio_format: &str) -> Option<PcmFormat> {
+    let format = audio_format.to_ascii_lowercase();
+    let (block_align, rate) = if format == "mulaw" || format == "alaw" {
+        (1, "8k")
+    } else if let Some(rate) = format
+        .strip_prefix("lsb")
+        .or_else(|| format.strip_prefix("msb"))
+    {
+        (2, rate)
+    } else {
+        (2, format.as_str())
+    };
+    let sample_rate = match rate {
+        "8k" => 8000,
+        "11k" => 11025,
+        "16k" => 16000,
+        "22k" => 22050,
+        "32k" => 32000,
+        "44k" => 44100,
+        "48k" => 48000,
+        _ => return None,
+    };
+    Some(PcmFormat {
+        sample_rate,
+        block_align,
+    })
+}
+
+pub fn is_wave_format(audio_format: &str) -> bool {
+    matches!(audio_format.to_ascii_lowercase().as_str(), "wav" | "wave")
+}
+
+fn read_u16(bytes: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes([bytes[at], bytes[at + 1]])
+}
+
+fn read_u32(bytes: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]])
+}
+
+// returns None when the stream does not start with a RIFF/WAVE header
+pub fn read_wav_header<R: Read>(reader: &mut R) -> Result<Option<WavHeader>, String> {
+    let mut bytes = vec![0; 12];
+    if reader.read_exact(&mut bytes).is_err() || &bytes[0..4] != b"RIFF" || &bytes[8..12] != b"WAVE"
+    {
+        return Ok(None);
+    }
+
+    let mut format = None;
+    loop {
+        let at = bytes.len();
+        bytes.resize(at + 8, 0);
+        reader
+            .read_exact(&mut bytes[at..])
+            .map_err(|e| format!("failed to read wav header: {}", e))?;
+        let id = bytes[at..at + 4].to_vec();
+        let size = read_u32(&bytes, at + 4) as u64;
+        if id == b"data" {
+            let (format, format_tag, channels, bits_per_sample) =
+                format.ok_or("failed to read wav header: fmt chunk is missing")?;
+            return Ok(Some(WavHeader {
+                format,
+                format_tag,
+                channels,
+                bits_per_sample,
+                bytes,
+                data_size: size,
+            }));
+        }
+
+        // chunks are padded to even size
+        let body = at + 8;
+        bytes.resize(body + (size + size % 2) as usize, 0);
+        reader
+            .read_exact(&mut bytes[body..])
+            .map_err(|e| format!("failed to read wav header: {}", e))?;
+        if id == b"fmt " {
+            if size < 16 {
+                return Err("failed to read wav header: fmt chunk is too short".to_string());
+            }
+            format = Some((
+                PcmFormat {
+                    sample_rate: read_u32(&bytes, body + 4),
+                    block_align: read_u16(&bytes, body + 12) as u32,
+                },
+                read_u16(&bytes, body),
+                read_u16(&bytes, body + 2),
+                read_u16(&bytes, body + 14),
+            ));
+        }
+    }
+}
+
+// opens audio for streaming. with a time range the samples before the start are skipped by seeking,
+// which needs a known byte layout: PCM formats, or WAV whose header describes it.
+pub fn open(path: &str, audio_format: &str, range: &TimeRange) -> Result<Box<dyn Read>, String> {
+    let mut file = File::open(path).map_err(|e| format!("failed to open file: {}", e))?;
+    if range.is_full() {
+        return Ok(Box::new(BufReader::new(file)));
+    }
+
+    let header = read_wav_header(&mut file)?;
+    let (format, data_start, data_size) = match &header {
+        Some(header) => (
+            header.format,
+            header.bytes.len() as u64,
+            Some(header.data_size),
+        ),
+        None => match pcm_format_of(audio_format) {
+            Some(format) => (format, 0, None),
+            None => {
+                return Err(format!(
+                    "--start, --end and --duration need PCM (e.g. 16k, LSB8K) or WAV audio but audio format is \"{}\"",
+                    audio_format
+                ))
+            }
+        },
+    };
+    let length = seek_range(&mut file, &format, data_start, data_size, range)?;
+    // the header sent with wav describes only the range
+    let prefix = match header {
+        Some(header) if is_wave_format(audio_format) => header.with_data_size(length),
+        _ => Vec::new(),
+    };
+    Ok(Box::new(
+        Cursor::new(prefix).chain(BufReader::new(file).take(length)),
+    ))
+}
+
+// bytes per second of the streamed audio and the size of the WAV header sent before the samples
+pub fn byte_rate(path: &str, audio_format: &str) -> Result<(u64, u64), String> {
+    let (format, header_size) = if is_wave_format(audio_format) {
+        let mut file = File::open(path).map_err(|e| format!("failed to open file: {}", e))?;
+        let header = read_wav_header(&mut file)?
+            .ok_or_else(|| format!("audio format is wav but {} is not WAV", path))?;
+        (header.format, header.bytes.len() as u64)
+    } else {
+        match pcm_format_of(audio_format) {
+            Some(format) => (format, 0),
+            None => {
+                return Err(format!(
+                    "realtime pacing needs PCM (e.g. 16k, LSB8K) or WAV audio but audio format is \"{}\"",
+                    audio_format
+                ))
+            }
+        }
+    };
+    Ok((
+        format.sample_rate as u64 * format.block_align as u64,
+        header_size,
+    ))
+}
+
+fn describe(header: &WavHeader) -> String {
+    format!(
+        "{} Hz, {} ch, {} bit, format {}",
+        header.format.sample_rate, header.channels, header.bits_per_sample, header.format_tag
+    )
+}
+
+// opens several files to stream back to back as one audio.
+// WAV files must share the same format; only the header of the first file is sent (with the total size)
+// and only when the audio format is wav. returns the time span of each file in the stream.
+pub fn open_files(
+    paths: &[String],
+    audio_format: &str,
+) -> Result<(Box<dyn Read>, Vec<SourceFile>), String> {
+    let mut first: Option<WavHeader> = None;
+    let mut format = None;
+    let mut parts = Vec::new();
+    for (index, path) in paths.iter().enumerate() {
+        let mut file =
+            File::open(path).map_err(|e| format!("failed to open file {}: {}", path, e))?;
+        let file_size = file
+            .metadata()
+            .map_err(|e| format!("failed to read file metadata: {}", e))?
+            .len();
+        let header = read_wav_header(&mut file)?;
+        let data_size = match (&header, &first) {
+            (Some(header), Some(first)) => {
+                if describe(header) != describe(first) {
+                    return Err(format!(
+                        "{} ({}) does not match the format of {} ({})",
+                        path,
+                        describe(header),
+                        paths[0],
+                        describe(first)
+                    ));
+                }
+                header.data_size.min(file_size - header.bytes.len() as u64)
+            }
+            (Some(header), None) if index == 0 => {
+                format = Some(header.format);
+                header.data_size.min(file_size - header.bytes.len() as u64)
+            }
+            (None, None) => {
+                if index == 0 {
+                    format = pcm_format_of(audio_format);
+                }
+                file.seek(SeekFrom::Start(0))
+                    .map_err(|e| format!("failed to seek audio: {}", e))?;
+                file_size
+            }
+            _ => {
+                return Err(format!(
+                    "{} and {} cannot be concatenated: only one of them is WAV",
+                    paths[0], path
+                ))
+            }
+        };
+        if index == 0 {
+            first = header;
+        }
+        parts.push((path, file, data_size));
+    }
+    let format = format.ok_or_else(|| {
+        format!(
+            "concatenating files needs PCM (e.g. 16k, LSB8K) or WAV audio but audio format is \"{}\"",
+            audio_format
+        )
+    })?;
+    if format.block_align == 0 || format.sample_rate == 0 {
+        return Err("failed to read wav header: invalid sample format".to_string());
+    }
+
+    let total: u64 = parts.iter().map(|(_, _, size)| size).sum();
+    let prefix = match &first {
+        Some(header) if is_wave_format(audio_format) => header.with_data_size(total),
+        _ => Vec::new(),
+    };
+
+    let mut files = Vec::new();
+    let mut reader: Box<dyn Read> = Box::new(Cursor::new(prefix));
+    let millis =
+        |bytes: u64| bytes * 1000 / (format.sample_rate as u64 * format.block_align as u64);
+    let mut offset = 0;
+    for (path, file, data_size) in parts {
+        files.push(SourceFile {
+            path: path.clone(),
+            starttime: millis(offset),
+            endtime: millis(offset + data_size),
+        });
+        offset += data_size;
+        reader = Box::new(reader.chain(BufReader::new(file).take(data_size)));
+    }
+    Ok((reader, files))
+}
+
+// seeks to the start of the range and returns the byte length of the range
+fn seek_range(
+    file: &mut File,
+    format: &PcmFormat,
+    data_start: u64,
+    data_size: Option<u64>,
+    range: &TimeRange,
+) -> Result<u64, String> {
+    if format.block_align == 0 || format.sample_rate == 0 {
+        return Err("failed to read wav header: invalid sample format".to_string());
+    }
+
+    let file_size = file
+        .metadata()
+        .map_err(|e| format!("failed to read file metadata: {}", e))?
+        .len();
+    let data_size = data_size
+        .unwrap_or(u64::MAX)
+        .min(file_size.saturating_sub(data_start));
+    let offset = match format.byte_offset(range.start) {
+        Some(offset) if offset < data_size => offset,
+        _ => {
+            return Err(format!(
+                "--start ({} ms) is beyond the end of the audio",
+                range.start
+            ))
+        }
+    };
+    // a range past the end of the audio stops at the end of the data chunk
+    let end = range
+        .end
+        .and_then(|end| format.byte_offset(end))
+        .unwrap_or(data_size)
+        .min(data_size);
+    let length = end - offset;
+
+    file.seek(SeekFrom::Start(data_start + offset))
+        .map_err(|e| format!("failed to seek audio: {}", e))?;
+    Ok(length)
+}
+
+const WAVE_FORMAT_PCM: u16 = 1;
+const WAVE_FORMAT_EXTENSIBLE: u16 = 0xfffe;
+
+fn read_multichannel_header(file: &mut File, path: &str) -> Result<WavHeader, String> {
+    let header = read_wav_header(file)?
+        .ok_or_else(|| format!("splitting channels needs WAV audio but {} is not WAV", path))?;
+    if !matches!(header.format_tag, WAVE_FORMAT_PCM | WAVE_FORMAT_EXTENSIBLE)
+        || header.bits_per_sample != 16
+    {
+        return Err(format!(
+            "splitting channels needs 16 bit PCM WAV but {} is {} bit (format {})",
+            path, header.bits_per_sample, header.format_tag
+        ));
+    }
+    Ok(header)
+}
+
+// number of channels and the audio format of a session for one of them (16 bit little endian mono)
+pub fn channel_layout(path: &str) -> Result<(usize, String), String> {
+    let mut file = File::open(path).map_err(|e| format!("failed to open file: {}", e))?;
+    let header = read_multichannel_header(&mut file, path)?;
+    let audio_format = match header.format.sample_rate {
+        8000 => "8K",
+        16000 => "16K",
+        11025 => "LSB11K",
+        22050 => "LSB22K",
+        32000 => "LSB32K",
+        44100 => "LSB44K",
+        48000 => "LSB48K",
+        rate => return Err(format!("sample rate {} Hz is not supported", rate)),
+    };
+    Ok((header.channels as usize, audio_format.to_string()))
+}
+
+pub fn open_channel(
+    path: &str,
+    range: &TimeRange,
+    channel: usize,
+) -> Result<Box<dyn Read>, String> {
+    let mut file = File::open(path).map_err(|e| format!("failed to open file: {}", e))?;
+    let header = read_multichannel_header(&mut file, path)?;
+    if channel >= header.channels as usize {
+        return Err(format!(
+            "channel {} does not exist: {} has {} channels",
+            channel + 1,
+            path,
+            header.channels
+        ));
+    }
+    let length = seek_range(
+        &mut file,
+        &header.format,
+        header.bytes.len() as u64,
+        Some(header.data_size),
+        range,
+    )?;
+    Ok(Box::new(ChannelReader {
+        inner: BufReader::new(file).take(length),
+        offset: channel * SAMPLE_BYTES,
+        block_align: header.format.block_align as usize,
+        pending: Vec::new(),
+    }))
+}
+
+const SAMPLE_BYTES: usize = 2;
+
+// extracts the samples of one channel from interleaved 16 bit PCM
+struct ChannelReader<R> {
+    inner: R,
+    offset: usize,
+    block_align: usize,
+    pending: Vec<u8>,
+}
+
+impl<R: Read> Read for ChannelReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let frames = buf.len() / SAMPLE_BYTES;
+        if frames == 0 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "buffer is smaller than a sample",
+            ));
+        }
+        let mut raw = vec![0; frames * self.block_align];
+        loop {
+            // less than a frame is left pending between reads, so there is always room
+            let room = frames * self.block_align - self.pending.len();
+            let size = self.inner.read(&mut raw[..room])?;
+            self.pending.extend_from_slice(&raw[..size]);
+
+            let complete = (self.pending.len() / self.block_align).min(frames);
+            if complete == 0 {
+                if size == 0 {
+                    return Ok(0);
+                }
+                continue;
+            }
+            for frame in 0..complete {
+                let at = frame * self.block_align + self.offset;
+                buf[frame * SAMPLE_BYTES..(frame + 1) * SAMPLE_BYTES]
+                    .copy_from_slice(&self.pending[at..at + SAMPLE_BYTES]);
+            }
+            self.pending.drain(..complete * self.block_align);
+            return Ok(complete * SAMPLE_BYTES);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 16 bit PCM WAV with a LIST chunk of odd size between fmt and data
+    fn wav(channels: u16, sample_rate: u32, samples: &[u8]) -> Vec<u8> {
+        let block_align = channels * 2;
+        let mut fmt = Vec::new();
+        fmt.extend_from_slice(&WAVE_FORMAT_PCM.to_le_bytes());
+        fmt.extend_from_slice(&channels.to_le_bytes());
+        fmt.extend_from_slice(&sample_rate.to_le_bytes());
+        fmt.extend_from_slice(&(sample_rate * block_align as u32).to_le_bytes());
+        fmt.extend_from_slice(&block_align.to_le_bytes());
+        fmt.extend_from_slice(&16u16.to_le_bytes());
+
+        let mut body = b"WAVE".to_vec();
+        for (id, chunk) in [(b"fmt ", &fmt[..]), (b"LIST", &b"abc"[..])].iter() {
+            body.extend_from_slice(*id);
+            body.extend_from_slice(&(chunk.len() as u32).to_le_bytes());
+            body.extend_from_slice(chunk);
+            if chunk.len() % 2 == 1 {
+                body.push(0);
+            }
+        }
+        body.extend_from_slice(b"data");
+        body.extend_from_slice(&(samples.len() as u32).to_le_bytes());
+        body.extend_from_slice(samples);
+
+        let mut bytes = b"RIFF".to_vec();
+        bytes.extend_from_slice(&(body.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&body);
+        bytes
+    }
+
+    #[test]
+    fn parse_time_accepts_seconds_and_clock() {
+        assert_eq!(parse_time("90").unwrap(), 90_000);
+        assert_eq!(parse_time(" 90.5 ").unwrap(), 90_500);
+        assert_eq!(parse_time("1:30").unwrap(), 90_000);
+        assert_eq!(parse_time("01:02:03.5").unwrap(), 3_723_500);
+        assert_eq!(parse_time("0").unwrap(), 0);
+    }
+
+    #[test]
+    fn parse_time_rejects_invalid() {
+        for s in &[
+            "",
+            "abc",
+            "1:x",
+            "-1",
+            "inf",
+            "NaN",
+            "1e400",
+            "1e12",
+            "9000:00:00",
+        ] {
+            assert!(parse_time(s).is_err(), "{} was accepted", s);
+        }
+    }
+
+    #[test]
+    fn time_range_parse() {
+        let range = TimeRange::parse(Some("1:00"), None, Some("30")).unwrap();
+        assert_eq!(range.start, 60_000);
+        assert_eq!(range.end, Some(90_000));
+        assert_eq!(range.cache_key(), "60000-90000");
+
+        let range = TimeRange::parse(None, Some("10"), None).unwrap();
+        assert_eq!((range.start, range.end), (0, Some(10_000)));
+        assert!(TimeRange::parse(None, None, None).unwrap().is_full());
+
+        assert!(TimeRange::parse(Some("10"), Some("5"), None).is_err());
+        assert!(TimeRange::parse(Some("10"), None, Some("0")).is_err());
+        assert!(TimeRange::parse(Some("inf"), None, None).is_err());
+    }
+
+    #[test]
+    fn byte_offset_checks_overflow() {
+        let format = PcmFormat {
+            sample_rate: 16000,
+            block_align: 4,
+        };
+        assert_eq!(format.byte_offset(1500), Some(96_000));
+        assert_eq!(format.byte_offset(u64::MAX / 1000), None);
+    }
+
+    #[test]
+    fn pcm_format_of_known_formats() {
+        let format = |rate, block_align| {
+            Some(PcmFormat {
+                sample_rate: rate,
+                block_align,
+            })
+        };
+        assert_eq!(pcm_format_of("16k"), format(16000, 2));
+        assert_eq!(pcm_format_of("LSB44K"), format(44100, 2));
+        assert_eq!(pcm_format_of("msb22k"), format(22050, 2));
+        assert_eq!(pcm_format_of("mulaw"), format(8000, 1));
+        assert_eq!(pcm_format_of("wav"), None);
+        assert_eq!(pcm_format_of("lsb12k"), None);
+    }
+
+    #[test]
+    fn read_wav_header_skips_chunks() {
+        let bytes = wav(2, 8000, &[1, 2, 3, 4, 5, 6, 7, 8]);
+        let mut reader = Cursor::new(&bytes);
+        let header = read_wav_header(&mut reader).unwrap().unwrap();
+        assert_eq!(
+            header.format,
+            PcmFormat {
+                sample_rate: 8000,
+                block_align: 4
+            }
+        );
+        assert_eq!((header.channels, header.bits_per_sample), (2, 16));
+        assert_eq!(header.data_size, 8);
+        assert_eq!(header.bytes, bytes[..bytes.len() - 8]);
+
+        // the reader is left at the first sample
+        let mut rest = Vec::new();
+        reader.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, [1, 2, 3, 4, 5, 6, 7, 8]);
+    }
+
+    #[test]
+    fn read_wav_header_rejects_other_files() {
+        assert!(read_wav_header(&mut Cursor::new(b"OggS\0\0\0\0\0\0\0\0"))
+            .unwrap()
+            .is_none());
+        assert!(read_wav_header(&mut Cursor::new(b"RIFF"))
+            .unwrap()
+            .is_none());
+        assert!(read_wav_header(&mut Cursor::new(b"RIFF\0\0\0\0WAVEdata\0\0\0\0")).is_err());
+    }
+
+    #[test]
+    fn with_data_size_patches_sizes() {
+        let bytes = wav(1, 16000, &[0; 32]);
+        let header = read_wav_header(&mut Cursor::new(&bytes)).unwrap().unwrap();
+        let patched = header.with_data_size(10);
+        assert_eq!(read_u32(&patched, 4) as usize, patched.len() - 8 + 10);
+        assert_eq!(read_u32(&patched, patched.len() - 4), 10);
+    }
+
+    #[test]
+    fn open_range_sends_header_of_the_range() {
+        // 1 second of 8 kHz mono, sample i is i % 256 in the low byte
+        let samples: Vec<u8> = (0..8000u32).flat_map(|i| vec![i as u8, 0]).collect();
+        let path = std::env::temp_dir().join(format!("{}-range-test.wav", crate_name!()));
+        std::fs::write(&path, wav(1, 8000, &samples)).unwrap();
+        let path = path.to_str().unwrap();
+
+        let range = TimeRange::parse(Some("0.25"), None, Some("0.5")).unwrap();
+        let mut audio = Vec::new();
+        open(path, "wav", &range)
+            .unwrap()
+            .read_to_end(&mut audio)
+            .unwrap();
+        let header = read_wav_header(&mut Cursor::new(&audio)).unwrap().unwrap();
+        assert_eq!(header.data_size, 8000);
+        assert_eq!(read_u32(&audio, 4) as usize, audio.len() - 8);
+        assert_eq!(&audio[header.bytes.len()..], &samples[4000..12000]);
+
+        // the end is clamped to the data chunk, and pcm formats get no header
+        let range = TimeRange::parse(Some("0.5"), Some("10"), None).unwrap();
+        let mut audio = Vec::new();
+        open(path, "8k", &range)
+            .unwrap()
+            .read_to_end(&mut audio)
+            .unwrap();
+        assert_eq!(audio, &samples[8000..]);
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    // returns at most `chunk` bytes per read so that frames are split across reads
+    struct Trickle<'a> {
+        bytes: &'a [u8],
+        chunk: usize,
+    }
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let size = self.chunk.min(buf.len()).min(self.bytes.len());
+            buf[..size].copy_from_slice(&self.bytes[..size]);
+            self.bytes = &self.bytes[size..];
+            Ok(size)
+        }
+    }
+
+    #[test]
+    fn channel_reader_handles_partial_frames() {
+        // 3 channels, sample bytes are [frame, channel]
+        let interleaved: Vec<u8> = (0..50u8)
+            .flat_map(|frame| (0..3u8).flat_map(move |channel| vec![frame, channel]))
+            .collect();
+        for &(chunk, buffer) in &[(1, 2), (5, 4), (7, 64), (300, 6), (1000, 1000)] {
+            let mut reader = ChannelReader {
+                inner: Trickle {
+                    bytes: &interleaved,
+                    chunk,
+                },
+                offset: 2 * SAMPLE_BYTES,
+                block_align: 3 * SAMPLE_BYTES,
+                pending: Vec::new(),
+            };
+            let mut samples = Vec::new();
+            let mut buf = vec![0; buffer];
+            loop {
+                let size = reader.read(&mut buf).unwrap();
+                if size == 0 {
+                    break;
+                }
+                assert_eq!(size % SAMPLE_BYTES, 0);
+                samples.extend_from_slice(&buf[..size]);
+            }
+            let expected: Vec<u8> = (0..50u8).flat_map(|frame| vec![frame, 2]).collect();
+            assert_eq!(samples, expected, "chunk {} buffer {}", chunk, buffer);
+        }
+    }
+
+    #[test]
+    fn channel_reader_drops_a_trailing_partial_frame() {
+        let mut reader = ChannelReader {
+            inner: Trickle {
+                bytes: &[1, 0, 2, 0, 3, 0, 4],
+                chunk: 3,
+            },
+            offset: 0,
+            block_align: 4,
+            pending: Vec::new(),
+        };
+        let mut samples = Vec::new();
+        reader.read_to_end(&mut samples).unwrap();
+        assert_eq!(samples, [1, 0]);
+        assert!(reader.read(&mut [0; 1]).is_err());
+    }
+}
@@ -0,0 +1,282 @@
+use crate::ami::{JsonOutput, SCommandOption};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use url::Url;
+
+pub const DEFAULT_MAX_SIZE: &str = "512M";
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct CacheEntry {
+    pub created_at: u64,
+    pub audio_path: String,
+    pub endpoint: String,
+    pub output: JsonOutput,
+}
+
+#[derive(Debug, Clone)]
+pub struct CacheFile {
+    pub key: String,
+    pub path: PathBuf,
+    pub size: u64,
+    pub modified: u64,
+}
+
+pub fn resolve_cache_dir() -> Result<PathBuf, String> {
+    if let Some(dir) = dirs::cache_dir() {
+        Ok(dir.join(crate_name!()).join("results"))
+    } else {
+        Err("failed to resolve cache directory".to_string())
+    }
+}
+
+// audio files concatenated into one session are separated in the hash, which keeps the key of a single file
+pub fn compute_key(
+    audio_file_paths: &[String],
+    option: &SCommandOption,
+    endpoint: &Url,
+    variant: &str,
+) -> Result<String, String> {
+    let mut hasher = Sha256::new();
+    let mut buf = [0; 65536];
+    for (index, audio_file_path) in audio_file_paths.iter().enumerate() {
+        if index > 0 {
+            hasher.update(b"\0file\0");
+        }
+        let mut file = File::open(audio_file_path)
+            .map_err(|e| format!("failed to open file for cache key: {}", e))?;
+        loop {
+            let size = file
+                .read(&mut buf)
+                .map_err(|e| format!("failed to read audio for cache key: {}", e))?;
+            if size == 0 {
+                break;
+            }
+            hasher.update(&buf[..size]);
+        }
+    }
+
+    let option = serde_json::to_string(&option.without_authorization())
+        .map_err(|e| format!("failed to serialize option for cache key: {}", e))?;
+    hasher.update(b"\0");
+    hasher.update(option.as_bytes());
+    hasher.update(b"\0");
+    hasher.update(endpoint.as_str().as_bytes());
+    // part of the audio (time range, channel). empty for the whole file to keep existing keys
+    if !variant.is_empty() {
+        hasher.update(b"\0");
+        hasher.update(variant.as_bytes());
+    }
+
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect())
+}
+
+fn entry_path(key: &str) -> Result<PathBuf, String> {
+    resolve_cache_dir().map(|dir| dir.join(format!("{}.json", key)))
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+pub fn load(key: &str) -> Result<Option<CacheEntry>, String> {
+    let path = entry_path(key)?;
+    if !path.exists() {
+        return Ok(None);
+    }
+    let text = std::fs::read_to_string(&path)
+        .map_err(|e| format!("failed to read cache {}: {}", path.display(), e))?;
+    match serde_json::from_str(&text) {
+        Ok(entry) => {
+            // pruning removes the oldest modified entries first, so a hit marks the entry as recently used
+            if let Err(e) = touch(&path) {
+                eprintln!("failed to update cache {}: {}", path.display(), e);
+            }
+            Ok(Some(entry))
+        }
+        Err(e) => {
+            eprintln!("ignore broken cache {}: {}", path.display(), e);
+            Ok(None)
+        }
+    }
+}
+
+fn touch(path: &Path) -> std::io::Result<()> {
+    File::options()
+        .write(true)
+        .open(path)?
+        .set_modified(SystemTime::now())
+}
+
+pub fn store(
+    key: &str,
+    audio_file_path: &str,
+    endpoint: &Url,
+    output: &JsonOutput,
+) -> Result<(), String> {
+    let entry = CacheEntry {
+        created_at: now(),
+        audio_path: audio_file_path.to_string(),
+        endpoint: endpoint.to_string(),
+        output: output.without_authorization(),
+    };
+    let text =
+        serde_json::to_string(&entry).map_err(|e| format!("failed to serialize cache: {}", e))?;
+
+    let dir = resolve_cache_dir()?;
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| format!("failed to create cache directory: {}", e))?;
+    let path = entry_path(key)?;
+    std::fs::write(&path, text)
+        .map_err(|e| format!("failed to write cache {}: {}", path.display(), e))
+}
+
+pub fn list() -> Result<Vec<CacheFile>, String> {
+    let dir = resolve_cache_dir()?;
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let entries =
+        std::fs::read_dir(&dir).map_err(|e| format!("failed to read cache directory: {}", e))?;
+
+    let mut files = Vec::new();
+    for entry in entries {
+        let entry = entry.map_err(|e| format!("failed to read cache directory: {}", e))?;
+        let path = entry.path();
+        if path.extension().and_then(|ext| ext.to_str()) != Some("json") {
+            continue;
+        }
+        let metadata = entry
+            .metadata()
+            .map_err(|e| format!("failed to read metadata of {}: {}", path.display(), e))?;
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let key = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or("")
+            .to_string();
+        files.push(CacheFile {
+            key,
+            path,
+            size: metadata.len(),
+            modified,
+        });
+    }
+    files.sort_by_key(|file| std::cmp::Reverse(file.modified));
+    Ok(files)
+}
+
+pub fn prune(max_size: Option<u64>, max_age_secs: Option<u64>) -> Result<Vec<CacheFile>, String> {
+    let now = now();
+    let mut total = 0;
+    let mut removed = Vec::new();
+    for file in list()? {
+        let is_expired =
+            matches!(max_age_secs, Some(age) if now.saturating_sub(file.modified) > age);
+        let is_over = matches!(max_size, Some(size) if total + file.size > size);
+        if is_expired || is_over {
+            std::fs::remove_file(&file.path)
+                .map_err(|e| format!("failed to remove cache {}: {}", file.path.display(), e))?;
+            removed.push(file);
+        } else {
+            total += file.size;
+        }
+    }
+    Ok(removed)
+}
+
+pub fn clear() -> Result<usize, String> {
+    let files = list()?;
+    for file in &files {
+        std::fs::remove_file(&file.path)
+            .map_err(|e| format!("failed to remove cache {}: {}", file.path.display(), e))?;
+    }
+    Ok(files.len())
+}
+
+pub fn parse_size(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    let (number, unit) = match s.find(|c: char| !c.is_ascii_digit()) {
+        Some(index) => (&s[..index], s[index..].trim()),
+        None => (s, ""),
+    };
+    let number: u64 = number
+        .parse()
+        .map_err(|e| format!("failed to parse size \"{}\": {}", s, e))?;
+    let scale = match unit
+        .to_ascii_uppercase()
+        .trim_end_matches("IB")
+        .trim_end_matches('B')
+    {
+        "" => 1,
+        "K" => 1 << 10,
+        "M" => 1 << 20,
+        "G" => 1 << 30,
+        _ => return Err(format!("failed to parse size \"{}\": unknown unit", s)),
+    };
+    number
+        .checked_mul(scale)
+        .ok_or_else(|| format!("failed to parse size \"{}\": too large", s))
+}
+
+// the cache is best effort: the results have been written to the outputs when it is updated
+pub fn warn_on_error<T>(result: Result<T, String>) {
+    if let Err(e) = result {
+        eprintln!("warning: failed to update the cache: {}", e);
+    }
+}
+
+pub fn format_size(size: u64) -> String {
+    if size >= 1 << 20 {
+        format!("{:.1}M", size as f64 / (1 << 20) as f64)
+    } else if size >= 1 << 10 {
+        format!("{:.1}K", size as f64 / (1 << 10) as f64)
+    } else {
+        format!("{}B", size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_size_units() {
+        assert_eq!(parse_size("512"), Ok(512));
+        assert_eq!(parse_size("2K"), Ok(2 << 10));
+        assert_eq!(parse_size("3 MiB"), Ok(3 << 20));
+        assert_eq!(parse_size("1gb"), Ok(1 << 30));
+        assert_eq!(parse_size(" 10M "), Ok(10 << 20));
+    }
+
+    #[test]
+    fn parse_size_rejects_invalid() {
+        assert!(parse_size("").is_err());
+        assert!(parse_size("M").is_err());
+        assert!(parse_size("10T").is_err());
+        assert!(parse_size("-1").is_err());
+        assert!(parse_size("1.5G").is_err());
+    }
+
+    #[test]
+    fn parse_size_rejects_overflow() {
+        assert!(parse_size("18446744073709551615").is_ok());
+        assert!(parse_size("18446744073709551615K").is_err());
+        assert!(parse_size("17179869184G").is_err());
+    }
+}
@@ -0,0 +1,493 @@
+use crate::transcript::{join_tokens, Token, Transcript, Utterance};
+use regex::Regex;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FillerMode {
+    Keep,
+    Remove,
+    Tag,
+}
+
+impl FillerMode {
+    pub const NAMES: &'static [&'static str] = &["keep", "remove", "tag"];
+}
+
+impl FromStr for FillerMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "keep" => Ok(FillerMode::Keep),
+            "remove" => Ok(FillerMode::Remove),
+            "tag" => Ok(FillerMode::Tag),
+            _ => Err(format!("unknown filler mode \"{}\"", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PunctuationMode {
+    Keep,
+    Ascii,
+    Remove,
+}
+
+impl PunctuationMode {
+    pub const NAMES: &'static [&'static str] = &["keep", "ascii", "remove"];
+}
+
+impl FromStr for PunctuationMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "keep" => Ok(PunctuationMode::Keep),
+            "ascii" => Ok(PunctuationMode::Ascii),
+            "remove" => Ok(PunctuationMode::Remove),
+            _ => Err(format!("unknown punctuation mode \"{}\"", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Pattern {
+    Literal(String),
+    Regex(Regex),
+}
+
+#[derive(Debug, Clone)]
+pub struct Replacement {
+    pub pattern: Pattern,
+    pub replacement: String,
+}
+
+impl Replacement {
+    fn find_all(&self, text: &str) -> Vec<(usize, usize, String)> {
+        match &self.pattern {
+            Pattern::Literal(literal) => text
+                .match_indices(literal.as_str())
+                .map(|(start, s)| (start, start + s.len(), self.replacement.clone()))
+                .collect(),
+            Pattern::Regex(regex) => regex
+                .captures_iter(text)
+                .filter_map(|caps| {
+                    let m = caps.get(0)?;
+                    let mut replacement = String::new();
+                    caps.expand(&self.replacement, &mut replacement);
+                    Some((m.start(), m.end(), replacement))
+                })
+                .filter(|(start, end, _)| start < end)
+                .collect(),
+        }
+    }
+}
+
+pub fn load_replacements(path: &str) -> Result<Vec<Replacement>, String> {
+    let text =
+        std::fs::read_to_string(path).map_err(|e| format!("failed to read {}: {}", path, e))?;
+    let mut replacements = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        if line.trim().is_empty() || line.trim_start().starts_with('#') {
+            continue;
+        }
+        let (pattern, replacement) = match line.find(" => ") {
+            Some(i) => (&line[..i], &line[i + 4..]),
+            None => {
+                return Err(format!(
+                    "failed to parse {} line {}: expected <pattern> => <replacement>",
+                    path,
+                    index + 1
+                ))
+            }
+        };
+        // an empty match would apply at every position
+        let empty_error = || {
+            format!(
+                "failed to parse {} line {}: the pattern matches an empty string",
+                path,
+                index + 1
+            )
+        };
+        let pattern = if let Some(regex) = pattern.strip_prefix("re:") {
+            let regex = Regex::new(regex).map_err(|e| {
+                format!(
+                    "failed to parse regex in {} line {}: {}",
+                    path,
+                    index + 1,
+                    e
+                )
+            })?;
+            if regex.is_match("") {
+                return Err(empty_error());
+            }
+            Pattern::Regex(regex)
+        } else if pattern.is_empty() {
+            return Err(empty_error());
+        } else {
+            Pattern::Literal(pattern.to_string())
+        };
+        replacements.push(Replacement {
+            pattern,
+            replacement: replacement.to_string(),
+        });
+    }
+    Ok(replacements)
+}
+
+#[derive(Debug, Clone)]
+pub struct CleanupOptions {
+    pub filler: FillerMode,
+    pub punctuation: PunctuationMode,
+    pub is_normalize_width: bool,
+    pub replacements: Vec<Replacement>,
+}
+
+impl Default for CleanupOptions {
+    fn default() -> Self {
+        CleanupOptions {
+            filler: FillerMode::Keep,
+            punctuation: PunctuationMode::Keep,
+            is_normalize_width: false,
+            replacements: Vec::new(),
+        }
+    }
+}
+
+fn is_filler(token: &Token) -> bool {
+    let written = &token.written;
+    written.len() > 2 && written.starts_with('%') && written.ends_with('%')
+}
+
+fn is_punctuation(c: char) -> bool {
+    matches!(
+        c,
+        '、' | '。' | '，' | '．' | '？' | '！' | ',' | '.' | '?' | '!'
+    )
+}
+
+fn to_ascii_punctuation(c: char) -> char {
+    match c {
+        '、' | '，' => ',',
+        '。' | '．' => '.',
+        '？' => '?',
+        '！' => '!',
+        c => c,
+    }
+}
+
+pub fn normalize_width(s: &str) -> String {
+    s.chars()
+        .map(|c| match c {
+            '０'..='９' | 'Ａ'..='Ｚ' | 'ａ'..='ｚ' => {
+                std::char::from_u32(c as u32 - 0xfee0).unwrap_or(c)
+            }
+            c => c,
+        })
+        .collect()
+}
+
+impl CleanupOptions {
+    pub fn is_noop(&self) -> bool {
+        self.filler == FillerMode::Keep
+            && self.punctuation == PunctuationMode::Keep
+            && !self.is_normalize_width
+            && self.replacements.is_empty()
+    }
+
+    pub fn apply(&self, transcript: &mut Transcript) {
+        if self.is_noop() {
+            return;
+        }
+        for utterance in transcript.utterances.iter_mut() {
+            self.apply_utterance(utterance);
+        }
+    }
+
+    pub fn apply_text(&self, text: &str) -> String {
+        let mut utterance = Utterance::from_tokens(text_tokens(text));
+        self.apply_utterance(&mut utterance);
+        utterance.text
+    }
+
+    // utterances read back without recognizer tokens are normalized as text and keep no tokens
+    pub fn apply_imported(&self, utterance: &Utterance) -> Utterance {
+        let mut utterance = utterance.clone();
+        if utterance.tokens.is_empty() {
+            utterance.text = self.apply_text(&utterance.text);
+        } else {
+            self.apply_utterance(&mut utterance);
+        }
+        utterance
+    }
+
+    fn apply_utterance(&self, utterance: &mut Utterance) {
+        let mut tokens = Vec::new();
+        for token in utterance.tokens.drain(..) {
+            if let Some(token) = self.apply_token(token) {
+                tokens.push(token);
+            }
+        }
+        for replacement in &self.replacements {
+            tokens = apply_replacement(tokens, replacement);
+        }
+        utterance.text = join_tokens(tokens.iter().map(|token| token.written.as_str()));
+        utterance.tokens = tokens;
+    }
+
+    fn apply_token(&self, mut token: Token) -> Option<Token> {
+        if is_filler(&token) {
+            let inner = token.written[1..token.written.len() - 1].to_string();
+            match self.filler {
+                FillerMode::Keep => (),
+                FillerMode::Remove => return None,
+                FillerMode::Tag => token.written = format!("({})", inner),
+            }
+        }
+
+        token.written = match self.punctuation {
+            PunctuationMode::Keep => token.written,
+            PunctuationMode::Ascii => token.written.chars().map(to_ascii_punctuation).collect(),
+            PunctuationMode::Remove => token
+                .written
+                .chars()
+                .filter(|c| !is_punctuation(*c))
+                .collect(),
+        };
+        if self.is_normalize_width {
+            token.written = normalize_width(&token.written);
+        }
+
+        if token.written.is_empty() {
+            None
+        } else {
+            Some(token)
+        }
+    }
+}
+
+// splits plain text into tokens so that fillers (%...%) are handled like those in recognition results
+fn text_tokens(text: &str) -> Vec<Token> {
+    let mut words = Vec::new();
+    for word in text.split_whitespace() {
+        let mut rest = word;
+        while let Some(start) = rest.find('%') {
+            let end = match rest[start + 1..].find('%') {
+                Some(len) => start + len + 2,
+                None => break,
+            };
+            words.push(&rest[..start]);
+            words.push(&rest[start..end]);
+            rest = &rest[end..];
+        }
+        words.push(rest);
+    }
+    words
+        .into_iter()
+        .filter(|word| !word.is_empty())
+        .map(|word| Token {
+            written: word.to_string(),
+            spoken: String::new(),
+            confidence: 1.0,
+            starttime: 0,
+            endtime: 0,
+            speaker: None,
+        })
+        .collect()
+}
+
+fn apply_replacement(tokens: Vec<Token>, replacement: &Replacement) -> Vec<Token> {
+    let text: String = tokens.iter().map(|token| token.written.as_str()).collect();
+    let matches = replacement.find_all(&text);
+    if matches.is_empty() {
+        return tokens;
+    }
+
+    let mut offsets = Vec::with_capacity(tokens.len());
+    let mut offset = 0;
+    for token in &tokens {
+        offsets.push(offset);
+        offset += token.written.len();
+    }
+
+    let mut tokens = tokens;
+    for (start, end, replaced) in matches.into_iter().rev() {
+        let first = match (0..tokens.len()).find(|&i| offsets[i] + tokens[i].written.len() > start)
+        {
+            Some(i) => i,
+            None => continue,
+        };
+        let last = (first..tokens.len())
+            .take_while(|&i| offsets[i] < end)
+            .last()
+            .unwrap_or(first);
+
+        let written = format!(
+            "{}{}{}",
+            &tokens[first].written[..start - offsets[first]],
+            replaced,
+            &tokens[last].written[end - offsets[last]..]
+        );
+        let merged = Token {
+            written,
+            spoken: tokens[first..=last]
+                .iter()
+                .map(|token| token.spoken.as_str())
+                .collect(),
+            confidence: tokens[first..=last]
+                .iter()
+                .map(|token| token.confidence)
+                .fold(1.0, f32::min),
+            starttime: tokens[first].starttime,
+            endtime: tokens[last].endtime,
+            speaker: tokens[first].speaker.clone(),
+        };
+        if merged.written.is_empty() {
+            tokens.drain(first..=last);
+            offsets.drain(first..=last);
+        } else {
+            tokens.splice(first..=last, std::iter::once(merged));
+            offsets.drain(first + 1..=last);
+        }
+    }
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token(written: &str, starttime: u64, endtime: u64, confidence: f32) -> Token {
+        Token {
+            written: written.to_string(),
+            spoken: String::new(),
+            confidence,
+            starttime,
+            endtime,
+            speaker: None,
+        }
+    }
+
+    fn words(words: &[&str]) -> Vec<Token> {
+        words.iter().map(|word| token(word, 0, 0, 1.0)).collect()
+    }
+
+    fn written(tokens: &[Token]) -> Vec<&str> {
+        tokens.iter().map(|token| token.written.as_str()).collect()
+    }
+
+    fn apply(options: &CleanupOptions, tokens: Vec<Token>) -> Vec<Token> {
+        let mut utterance = Utterance::from_tokens(tokens);
+        options.apply_utterance(&mut utterance);
+        utterance.tokens
+    }
+
+    #[test]
+    fn literal_replacement_merges_spanned_tokens() {
+        let tokens = vec![
+            token("東京", 0, 100, 0.9),
+            token("都庁", 100, 300, 0.8),
+            token("へ", 300, 400, 1.0),
+        ];
+        let replacement = Replacement {
+            pattern: Pattern::Literal("京都".to_string()),
+            replacement: "KYOTO".to_string(),
+        };
+        let tokens = apply_replacement(tokens, &replacement);
+        assert_eq!(written(&tokens), vec!["東KYOTO庁", "へ"]);
+        assert_eq!((tokens[0].starttime, tokens[0].endtime), (0, 300));
+        assert_eq!(tokens[0].confidence, 0.8);
+        assert_eq!((tokens[1].starttime, tokens[1].endtime), (300, 400));
+    }
+
+    #[test]
+    fn regex_replacement_expands_captures() {
+        let tokens = vec![
+            token("第", 0, 100, 1.0),
+            token("12", 100, 200, 1.0),
+            token("章", 200, 300, 1.0),
+            token("です", 300, 400, 1.0),
+        ];
+        let replacement = Replacement {
+            pattern: Pattern::Regex(Regex::new(r"第(\d+)章").unwrap()),
+            replacement: "Chapter $1".to_string(),
+        };
+        let tokens = apply_replacement(tokens, &replacement);
+        assert_eq!(written(&tokens), vec!["Chapter 12", "です"]);
+        assert_eq!((tokens[0].starttime, tokens[0].endtime), (0, 300));
+    }
+
+    #[test]
+    fn replacement_to_empty_drops_tokens() {
+        let replacement = Replacement {
+            pattern: Pattern::Literal("あのー".to_string()),
+            replacement: String::new(),
+        };
+        let tokens = apply_replacement(words(&["あの", "ー", "はい"]), &replacement);
+        assert_eq!(written(&tokens), vec!["はい"]);
+    }
+
+    #[test]
+    fn load_replacements_rejects_empty_matches() {
+        let path = std::env::temp_dir().join(format!("{}-replacements-test.txt", crate_name!()));
+        let path = path.to_str().unwrap();
+
+        std::fs::write(path, "# comment\n\nre:a* => b\n").unwrap();
+        let error = load_replacements(path).unwrap_err();
+        assert!(error.contains("line 3"), "{}", error);
+        assert!(error.contains("empty string"), "{}", error);
+
+        std::fs::write(path, " => b\n").unwrap();
+        assert!(load_replacements(path).is_err());
+
+        std::fs::write(path, "re:a+ => b\nえー => \n").unwrap();
+        assert_eq!(load_replacements(path).unwrap().len(), 2);
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn fillers_are_removed_or_tagged() {
+        let mut options = CleanupOptions {
+            filler: FillerMode::Remove,
+            ..CleanupOptions::default()
+        };
+        let tokens = words(&["%えー%", "はい", "%"]);
+        assert_eq!(written(&apply(&options, tokens.clone())), vec!["はい", "%"]);
+
+        options.filler = FillerMode::Tag;
+        assert_eq!(
+            written(&apply(&options, tokens)),
+            vec!["(えー)", "はい", "%"]
+        );
+    }
+
+    #[test]
+    fn punctuation_is_converted_or_removed() {
+        let mut options = CleanupOptions {
+            punctuation: PunctuationMode::Ascii,
+            ..CleanupOptions::default()
+        };
+        let tokens = words(&["はい", "、", "そう？", "。"]);
+        assert_eq!(
+            written(&apply(&options, tokens.clone())),
+            vec!["はい", ",", "そう?", "."]
+        );
+
+        options.punctuation = PunctuationMode::Remove;
+        assert_eq!(written(&apply(&options, tokens)), vec!["はい", "そう"]);
+    }
+
+    #[test]
+    fn normalize_width_converts_full_width_alphanumerics() {
+        assert_eq!(normalize_width("ＡＢｃ１２３円"), "ABc123円");
+        let options = CleanupOptions {
+            is_normalize_width: true,
+            ..CleanupOptions::default()
+        };
+        assert_eq!(
+            options.apply_text("ＮＨＫ の ３ 時"),
+            options.apply_text("NHK の 3 時")
+        );
+    }
+}
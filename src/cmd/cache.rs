@@ -0,0 +1,105 @@
+use crate::cache::{clear, format_size, list, load, parse_size, prune, resolve_cache_dir};
+use crate::cmd_base::CmdBase;
+use clap::{Arg, ArgMatches, SubCommand};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const SUBCMD_LIST: &str = "list";
+const SUBCMD_PRUNE: &str = "prune";
+const SUBCMD_CLEAR: &str = "clear";
+const ARG_MAX_SIZE: &str = "max_size";
+const ARG_MAX_AGE: &str = "max_age";
+
+pub struct Cache;
+
+impl CmdBase for Cache {
+    const NAME: &'static str = "cache";
+
+    fn subcommand<'a, 'b>() -> clap::App<'a, 'b> {
+        SubCommand::with_name(Self::NAME)
+            .about("manage local cache of recognition results")
+            .subcommand(SubCommand::with_name(SUBCMD_LIST).about("list cached results"))
+            .subcommand(
+                SubCommand::with_name(SUBCMD_PRUNE)
+                    .about("remove old cached results")
+                    .arg(
+                        Arg::with_name(ARG_MAX_SIZE)
+                            .long("max-size")
+                            .takes_value(true)
+                            .help("keep newest results up to this total size (e.g. 100M, 1G)"),
+                    )
+                    .arg(
+                        Arg::with_name(ARG_MAX_AGE)
+                            .long("max-age")
+                            .takes_value(true)
+                            .help("remove results not used for this number of days"),
+                    ),
+            )
+            .subcommand(SubCommand::with_name(SUBCMD_CLEAR).about("remove all cached results"))
+    }
+
+    fn run(args: &ArgMatches) -> Result<(), String> {
+        match args.subcommand() {
+            (SUBCMD_LIST, Some(_)) => show_list(),
+            (SUBCMD_PRUNE, Some(args)) => run_prune(args),
+            (SUBCMD_CLEAR, Some(_)) => {
+                let count = clear()?;
+                println!("removed {} cached results", count);
+                Ok(())
+            }
+            _ => Err("No subcommand chosen. Add --help | -h to view the subcommands.".to_string()),
+        }
+    }
+}
+
+fn show_list() -> Result<(), String> {
+    let files = list()?;
+    println!("cache directory: {}", resolve_cache_dir()?.display());
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let mut total = 0;
+    for file in &files {
+        total += file.size;
+        let (engine, audio_path) = match load(&file.key)? {
+            Some(entry) => (
+                entry.output.option().grammar_file_names.clone(),
+                entry.audio_path,
+            ),
+            None => ("-".to_string(), "-".to_string()),
+        };
+        println!(
+            "{}  {:>8}  {:>5}d  {}  {}",
+            &file.key[..12.min(file.key.len())],
+            format_size(file.size),
+            now.saturating_sub(file.modified) / 86400,
+            engine,
+            audio_path
+        );
+    }
+    println!("{} results, {}", files.len(), format_size(total));
+    Ok(())
+}
+
+fn run_prune(args: &ArgMatches) -> Result<(), String> {
+    let max_size = match args.value_of(ARG_MAX_SIZE) {
+        Some(v) => Some(parse_size(v)?),
+        None => None,
+    };
+    let max_age = match args.value_of(ARG_MAX_AGE) {
+        Some(v) => Some(
+            v.parse::<u64>()
+                .map_err(|e| format!("failed to parse --max-age: {}", e))?
+                .checked_mul(86400)
+                .ok_or("failed to parse --max-age: too large")?,
+        ),
+        None => None,
+    };
+    if max_size.is_none() && max_age.is_none() {
+        return Err("specify --max-size and/or --max-age".to_string());
+    }
+
+    let removed = prune(max_size, max_age)?;
+    println!("removed {} cached results", removed.len());
+    Ok(())
+}
@@ -0,0 +1,266 @@
+use crate::ami::SCommandOption;
+use crate::audio::TimeRange;
+use crate::cache;
+use crate::cleanup::{load_replacements, CleanupOptions, FillerMode, PunctuationMode};
+use crate::cmd::transcribe::{
+    resolve_authorization, ARG_ALLOW_UNKNOWN_ENGINE, ARG_API_KEY, ARG_AUTHORIZATION_TOKEN,
+};
+use crate::cmd_base::CmdBase;
+use crate::compare::{align_by_time, CompareReport, EngineResult, EngineSpec};
+use crate::engines::validate_grammar_file_names;
+use crate::eval::evaluate;
+use crate::import::load_utterances;
+use crate::output::{OutputFormat, OutputSpec, STDOUT_PATH};
+use crate::session::{run_session, SessionOptions};
+use crate::settings::Settings;
+use clap::{Arg, ArgMatches, SubCommand};
+use std::thread;
+
+const ARG_AUDIO_FILE: &str = "audio_file";
+const ARG_ENGINE: &str = "engine";
+const ARG_AUDIO_FORMAT: &str = "audio_format";
+const ARG_NO_LOG: &str = "no_log";
+const ARG_NO_CACHE: &str = "no_cache";
+const ARG_CACHE_MAX_SIZE: &str = "cache_max_size";
+const ARG_REFERENCE: &str = "reference";
+const ARG_RULES: &str = "rules";
+const ARG_ONLY_DIFFERENCES: &str = "only_differences";
+const ARG_FORMAT: &str = "format";
+const ARG_OUTPUT_FILE: &str = "output_file";
+
+const DEFAULT_AUDIO_FORMAT: &str = "16k";
+
+pub struct Compare;
+
+impl CmdBase for Compare {
+    const NAME: &'static str = "compare";
+
+    fn subcommand<'a, 'b>() -> clap::App<'a, 'b> {
+        SubCommand::with_name(Self::NAME)
+            .about("transcribe the same audio with several engines in parallel and compare the results")
+            .arg(
+                Arg::with_name(ARG_AUDIO_FILE)
+                    .long("audio-path")
+                    .required(true)
+                    .takes_value(true)
+                    .help("target audio file path"),
+            )
+            .arg(
+                Arg::with_name(ARG_ENGINE)
+                    .long("engine")
+                    .required(true)
+                    .takes_value(true)
+                    .multiple(true)
+                    .number_of_values(1)
+                    .allow_hyphen_values(true)
+                    .value_name("grammar[,format=<audio format>][,diarization]")
+                    .help("engine (grammar file names) and parameters of one session (repeatable, the first one is the baseline of alignment)"),
+            )
+            .arg(
+                Arg::with_name(ARG_AUDIO_FORMAT)
+                    .long("audio-format")
+                    .takes_value(true)
+                    .default_value(DEFAULT_AUDIO_FORMAT)
+                    .help("audio file format used by engines without format=")
+            )
+            .arg(
+                Arg::with_name(ARG_ALLOW_UNKNOWN_ENGINE)
+                    .long("allow-unknown-engine")
+                    .takes_value(false)
+                    .help("use engines that are not in the engine catalog"),
+            )
+            .arg(
+                Arg::with_name(ARG_API_KEY)
+                    .long("api-key")
+                    .takes_value(true)
+                    .help("AmiVoice Cloud Platform API KEY"),
+            )
+            .arg(
+                Arg::with_name(ARG_AUTHORIZATION_TOKEN)
+                    .long("authorization-token")
+                    .takes_value(true)
+                    .conflicts_with(ARG_API_KEY)
+                    .help("one-time service authorization token (instead of API KEY, only with a single --engine)"),
+            )
+            .arg(
+                Arg::with_name(ARG_NO_LOG)
+                    .long("no-log")
+                    .takes_value(false)
+                    .help("flag of saving audio file and recognition result"),
+            )
+            .arg(
+                Arg::with_name(ARG_NO_CACHE)
+                    .long("no-cache")
+                    .takes_value(false)
+                    .help("always recognize audio without using cached results"),
+            )
+            .arg(
+                Arg::with_name(ARG_CACHE_MAX_SIZE)
+                    .long("cache-max-size")
+                    .takes_value(true)
+                    .default_value(cache::DEFAULT_MAX_SIZE)
+                    .help("total size limit of cached results (e.g. 512M, 2G)"),
+            )
+            .arg(
+                Arg::with_name(ARG_REFERENCE)
+                    .long("reference")
+                    .takes_value(true)
+                    .help("correct transcript to score every engine against (same as eval --reference)"),
+            )
+            .arg(
+                Arg::with_name(ARG_RULES)
+                    .long("rules")
+                    .takes_value(true)
+                    .requires(ARG_REFERENCE)
+                    .help("normalization rules applied before scoring (same as eval --rules)"),
+            )
+            .arg(
+                Arg::with_name(ARG_ONLY_DIFFERENCES)
+                    .long("only-differences")
+                    .takes_value(false)
+                    .help("text report: show only rows where the results differ"),
+            )
+            .arg(
+                Arg::with_name(ARG_FORMAT)
+                    .long("format")
+                    .takes_value(true)
+                    .possible_values(&["text", "json"])
+                    .default_value("text")
+                    .help("format of the report"),
+            )
+            .arg(
+                Arg::with_name(ARG_OUTPUT_FILE)
+                    .long("output-file")
+                    .takes_value(true)
+                    .default_value(STDOUT_PATH)
+                    .help("report file path (- for stdout)"),
+            )
+    }
+
+    fn run(args: &ArgMatches) -> Result<(), String> {
+        let audio_file_path = args.value_of(ARG_AUDIO_FILE).unwrap().to_string();
+        let audio_format = args.value_of(ARG_AUDIO_FORMAT).unwrap();
+        let is_with_log = !args.is_present(ARG_NO_LOG);
+        let is_no_cache = args.is_present(ARG_NO_CACHE);
+        let cache_max_size = cache::parse_size(args.value_of(ARG_CACHE_MAX_SIZE).unwrap())?;
+        let engines = args
+            .values_of(ARG_ENGINE)
+            .unwrap()
+            .map(|spec| spec.parse())
+            .collect::<Result<Vec<EngineSpec>, String>>()?;
+        // a one-time token is consumed by the first session that uses it
+        if engines.len() > 1 && args.is_present(ARG_AUTHORIZATION_TOKEN) {
+            return Err(
+                "--authorization-token can only be used with a single --engine because it is valid for one session. use --api-key"
+                    .to_string(),
+            );
+        }
+        if !args.is_present(ARG_ALLOW_UNKNOWN_ENGINE) {
+            let settings = Settings::load()?;
+            for engine in &engines {
+                validate_grammar_file_names(&settings, &engine.grammar_file_names)?;
+            }
+        }
+
+        let normalize = CleanupOptions {
+            filler: FillerMode::Remove,
+            punctuation: PunctuationMode::Remove,
+            is_normalize_width: false,
+            replacements: match args.value_of(ARG_RULES) {
+                Some(path) => load_replacements(path)?,
+                None => Vec::new(),
+            },
+        };
+        let reference = match args.value_of(ARG_REFERENCE) {
+            Some(path) => Some(
+                load_utterances(path)?
+                    .iter()
+                    .map(|utterance| normalize.apply_imported(utterance))
+                    .collect::<Vec<_>>(),
+            ),
+            None => None,
+        };
+
+        let authorization = resolve_authorization(args)?;
+        let options = SessionOptions {
+            is_with_log,
+            is_no_cache,
+            ..SessionOptions::default()
+        };
+        let handles: Vec<_> = engines
+            .iter()
+            .map(|engine| {
+                let option = SCommandOption {
+                    audio_format: engine
+                        .audio_format
+                        .clone()
+                        .unwrap_or_else(|| audio_format.to_string()),
+                    grammar_file_names: engine.grammar_file_names.clone(),
+                    authorization: authorization.clone(),
+                    is_speaker_diarization: engine.is_speaker_diarization,
+                };
+                let options = options.clone();
+                let audio_file_path = audio_file_path.clone();
+                thread::spawn(move || {
+                    run_session(
+                        option,
+                        &options,
+                        &audio_file_path,
+                        &TimeRange::default(),
+                        None,
+                    )
+                })
+            })
+            .collect();
+
+        let mut results = Vec::new();
+        let mut transcripts = Vec::new();
+        for (engine, handle) in engines.iter().zip(handles) {
+            let output = handle
+                .join()
+                .unwrap_or_else(|_| Err("session thread panicked".to_string()));
+            let (transcript, error) = match output {
+                Ok(output) => (output.transcript(), output.error_message().cloned()),
+                Err(e) => (Default::default(), Some(e)),
+            };
+            let scores = reference.as_ref().map(|reference| {
+                let hypothesis: Vec<_> = transcript
+                    .utterances
+                    .iter()
+                    .map(|utterance| normalize.apply_imported(utterance))
+                    .collect();
+                let report = evaluate(reference, &hypothesis);
+                (report.cer, report.wer)
+            });
+            results.push(EngineResult {
+                label: engine.label.clone(),
+                error,
+                cer: scores.map(|(cer, _)| cer),
+                wer: scores.map(|(_, wer)| wer),
+            });
+            transcripts.push(transcript);
+        }
+        if !is_no_cache {
+            cache::warn_on_error(cache::prune(Some(cache_max_size), None));
+        }
+
+        let report = CompareReport {
+            rows: align_by_time(&transcripts),
+            engines: results,
+        };
+        let (format, text) = if args.value_of(ARG_FORMAT) == Some("json") {
+            (OutputFormat::Json, report.to_json()?)
+        } else {
+            (
+                OutputFormat::Text,
+                report.to_text(args.is_present(ARG_ONLY_DIFFERENCES)),
+            )
+        };
+        OutputSpec::new(format, args.value_of(ARG_OUTPUT_FILE).unwrap()).write(&text)?;
+
+        if report.engines.iter().all(|engine| engine.error.is_some()) {
+            return Err("all sessions failed".to_string());
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,102 @@
+use crate::cmd::configure::ENV_API_KEY;
+use crate::cmd::transcribe::setting_keys;
+use crate::cmd_base::CmdBase;
+use crate::fs::{load_api_key, resolve_config_path};
+use crate::settings::{Settings, Source};
+use clap::{Arg, ArgMatches, SubCommand};
+
+const SUBCMD_SHOW: &str = "show";
+const ARG_EFFECTIVE: &str = "effective";
+
+pub struct Config;
+
+impl CmdBase for Config {
+    const NAME: &'static str = "config";
+
+    fn subcommand<'a, 'b>() -> clap::App<'a, 'b> {
+        SubCommand::with_name(Self::NAME)
+            .about("inspect configuration")
+            .subcommand(
+                SubCommand::with_name(SUBCMD_SHOW)
+                    .about("show config files and transcribe defaults")
+                    .arg(
+                        Arg::with_name(ARG_EFFECTIVE)
+                            .long("effective")
+                            .takes_value(false)
+                            .help("show resolved settings and where each came from"),
+                    ),
+            )
+    }
+
+    fn run(args: &ArgMatches) -> Result<(), String> {
+        match args.subcommand() {
+            (SUBCMD_SHOW, Some(args)) => show(args.is_present(ARG_EFFECTIVE)),
+            _ => Err("No subcommand chosen. Add --help | -h to view the subcommands.".to_string()),
+        }
+    }
+}
+
+fn show(is_effective: bool) -> Result<(), String> {
+    let settings = Settings::load()?;
+    if is_effective {
+        show_effective(&settings)
+    } else {
+        show_layers(&settings)
+    }
+}
+
+fn show_layers(settings: &Settings) -> Result<(), String> {
+    println!("global config: {}", resolve_config_path()?.display());
+    if settings.layers().is_empty() {
+        println!("no config file found");
+    }
+    for layer in settings.layers() {
+        println!();
+        println!("[{}]", layer.source);
+        for (key, values) in &layer.values {
+            println!("{} = {}", key, values.join(", "));
+        }
+    }
+    Ok(())
+}
+
+fn show_effective(settings: &Settings) -> Result<(), String> {
+    let keys = setting_keys();
+    settings.check_keys(&keys)?;
+
+    let api_key = if let Ok(api_key) = std::env::var(ENV_API_KEY) {
+        Some((api_key, format!("environment variable {}", ENV_API_KEY)))
+    } else {
+        load_api_key()?.map(|api_key| {
+            (
+                api_key,
+                Source::Global(resolve_config_path().unwrap_or_default()).to_string(),
+            )
+        })
+    };
+    match api_key {
+        Some((api_key, source)) => println!("api_key = {} ({})", mask(&api_key), source),
+        None => println!("api_key is not set (prompt)"),
+    }
+
+    for (key, default) in keys {
+        if let Some(resolved) = settings.lookup(key) {
+            println!(
+                "{} = {} ({})",
+                key,
+                resolved.values.join(", "),
+                resolved.source
+            );
+        } else if let Some(default) = default {
+            println!("{} = {} ({})", key, default, Source::Default);
+        } else {
+            println!("{} is not set", key);
+        }
+    }
+    Ok(())
+}
+
+fn mask(api_key: &str) -> String {
+    let visible: String = api_key.chars().take(4).collect();
+    format!("{}****", visible)
+}
@@ -1,7 +1,13 @@
+use crate::ami::validate_api_key;
 use crate::cmd_base::CmdBase;
 use crate::fs::save_api_key;
-use crate::io::get_input;
-use clap::{ArgMatches, SubCommand};
+use crate::io::{get_secret_input, is_stdin_tty, read_all_stdin};
+use clap::{Arg, ArgGroup, ArgMatches, SubCommand};
+
+pub const ENV_API_KEY: &str = "ALSO_SPRACH_AMI_API_KEY";
+
+const ARG_API_KEY_STDIN: &str = "api_key_stdin";
+const ARG_FROM_ENV: &str = "from_env";
 
 pub struct Configure;
 
@@ -9,20 +15,51 @@ impl CmdBase for Configure {
     const NAME: &'static str = "configure";
 
     fn subcommand<'a, 'b>() -> clap::App<'a, 'b> {
-        SubCommand::with_name(Self::NAME).about("configure AmiVoice Cloud Platform API KEY")
+        SubCommand::with_name(Self::NAME)
+            .about("configure AmiVoice Cloud Platform API KEY")
+            .arg(
+                Arg::with_name(ARG_API_KEY_STDIN)
+                    .long("api-key-stdin")
+                    .takes_value(false)
+                    .help("read API KEY from stdin (non-interactive)"),
+            )
+            .arg(
+                Arg::with_name(ARG_FROM_ENV)
+                    .long("from-env")
+                    .takes_value(false)
+                    .help("read API KEY from environment variable ALSO_SPRACH_AMI_API_KEY"),
+            )
+            .group(ArgGroup::with_name("source").args(&[ARG_API_KEY_STDIN, ARG_FROM_ENV]))
     }
 
-    fn run(_: &ArgMatches) -> Result<(), String> {
-        let api_key = get_api_key()?;
+    fn run(args: &ArgMatches) -> Result<(), String> {
+        let api_key = if args.is_present(ARG_API_KEY_STDIN) {
+            read_all_stdin()?
+        } else if args.is_present(ARG_FROM_ENV) {
+            std::env::var(ENV_API_KEY)
+                .map_err(|e| format!("failed to read {}: {}", ENV_API_KEY, e))?
+                .trim()
+                .to_string()
+        } else {
+            get_api_key()?
+        };
+        validate_api_key(&api_key)?;
         save_api_key(&api_key)
     }
 }
 
 fn get_api_key() -> Result<String, String> {
+    if !is_stdin_tty() {
+        return Err(
+            "stdin is not a terminal. use --api-key-stdin or --from-env to configure non-interactively"
+                .to_string(),
+        );
+    }
+
     let mut s = String::new();
     while s.is_empty() {
-        s = get_input("AmiVoice Cloud Platform API KEY: ")?;
+        s = get_secret_input("AmiVoice Cloud Platform API KEY: ")?;
     }
 
-    Ok(s.clone())
+    Ok(s)
 }
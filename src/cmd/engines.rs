@@ -0,0 +1,101 @@
+use crate::cmd_base::CmdBase;
+use crate::engines::{catalog, Engine};
+use crate::settings::Settings;
+use clap::{Arg, ArgMatches, SubCommand};
+use serde::Serialize;
+
+const ARG_LANGUAGE: &str = "language";
+const ARG_FORMAT: &str = "format";
+
+#[derive(Serialize)]
+struct EngineEntry<'a> {
+    #[serde(flatten)]
+    engine: &'a Engine,
+    source: &'static str,
+}
+
+pub struct Engines;
+
+impl CmdBase for Engines {
+    const NAME: &'static str = "engines";
+
+    fn subcommand<'a, 'b>() -> clap::App<'a, 'b> {
+        SubCommand::with_name(Self::NAME)
+            .about("list known speech recognition engines (values of --grammar-file-names)")
+            .arg(
+                Arg::with_name(ARG_LANGUAGE)
+                    .long("language")
+                    .takes_value(true)
+                    .help("show only engines of this language (e.g. ja, en)"),
+            )
+            .arg(
+                Arg::with_name(ARG_FORMAT)
+                    .long("format")
+                    .takes_value(true)
+                    .possible_values(&["text", "json"])
+                    .default_value("text")
+                    .help("output format"),
+            )
+    }
+
+    fn run(args: &ArgMatches) -> Result<(), String> {
+        let engines: Vec<Engine> = catalog(&Settings::load()?)
+            .into_iter()
+            .filter(|engine| match args.value_of(ARG_LANGUAGE) {
+                Some(language) => engine.language == language,
+                None => true,
+            })
+            .collect();
+
+        if args.value_of(ARG_FORMAT) == Some("json") {
+            let entries: Vec<EngineEntry> = engines
+                .iter()
+                .map(|engine| EngineEntry {
+                    engine,
+                    source: source_of(engine),
+                })
+                .collect();
+            let text = serde_json::to_string_pretty(&entries)
+                .map_err(|e| format!("failed to serialize engines: {}", e))?;
+            println!("{}", text);
+            return Ok(());
+        }
+
+        let width = engines
+            .iter()
+            .map(|engine| engine.name.len())
+            .max()
+            .unwrap_or(0)
+            .max("NAME".len());
+        println!(
+            "{:width$}  {:8}  {:26}  {:12}  {:9}  SOURCE",
+            "NAME",
+            "LANGUAGE",
+            "DOMAIN",
+            "SAMPLE RATE",
+            "LOGGING",
+            width = width
+        );
+        for engine in &engines {
+            println!(
+                "{:width$}  {:8}  {:26}  {:12}  {:9}  {}",
+                engine.name,
+                engine.language,
+                engine.domain,
+                engine.sample_rates.join(","),
+                engine.logging.join(","),
+                source_of(engine),
+                width = width
+            );
+        }
+        Ok(())
+    }
+}
+
+fn source_of(engine: &Engine) -> &'static str {
+    if engine.is_custom {
+        "config"
+    } else {
+        "builtin"
+    }
+}
@@ -0,0 +1,112 @@
+use crate::cleanup::{load_replacements, CleanupOptions, FillerMode, PunctuationMode};
+use crate::cmd_base::CmdBase;
+use crate::eval::evaluate;
+use crate::import::load_utterances;
+use crate::output::{OutputFormat, OutputSpec, STDOUT_PATH};
+use crate::transcript::Utterance;
+use clap::{Arg, ArgMatches, SubCommand};
+
+const ARG_HYPOTHESIS: &str = "hypothesis";
+const ARG_REFERENCE: &str = "reference";
+const ARG_FILLER: &str = "filler";
+const ARG_PUNCTUATION: &str = "punctuation";
+const ARG_NORMALIZE_WIDTH: &str = "normalize_width";
+const ARG_RULES: &str = "rules";
+const ARG_FORMAT: &str = "format";
+const ARG_OUTPUT_FILE: &str = "output_file";
+
+pub struct Eval;
+
+impl CmdBase for Eval {
+    const NAME: &'static str = "eval";
+
+    fn subcommand<'a, 'b>() -> clap::App<'a, 'b> {
+        SubCommand::with_name(Self::NAME)
+            .about("compute character and word error rate of a transcription against a reference")
+            .arg(
+                Arg::with_name(ARG_HYPOTHESIS)
+                    .long("hypothesis")
+                    .required(true)
+                    .takes_value(true)
+                    .help("transcription to evaluate (any format written by transcribe or render)"),
+            )
+            .arg(
+                Arg::with_name(ARG_REFERENCE)
+                    .long("reference")
+                    .required(true)
+                    .takes_value(true)
+                    .help("correct transcript (text with one utterance per line, or any format written by this tool)"),
+            )
+            .arg(
+                Arg::with_name(ARG_FILLER)
+                    .long("filler")
+                    .takes_value(true)
+                    .possible_values(FillerMode::NAMES)
+                    .default_value("remove")
+                    .help("how to handle filler words such as %えー% before scoring"),
+            )
+            .arg(
+                Arg::with_name(ARG_PUNCTUATION)
+                    .long("punctuation")
+                    .takes_value(true)
+                    .possible_values(PunctuationMode::NAMES)
+                    .default_value("remove")
+                    .help("how to handle punctuation before scoring"),
+            )
+            .arg(
+                Arg::with_name(ARG_NORMALIZE_WIDTH)
+                    .long("normalize-width")
+                    .takes_value(false)
+                    .help("convert full-width alphanumerics to half-width before scoring"),
+            )
+            .arg(
+                Arg::with_name(ARG_RULES)
+                    .long("rules")
+                    .takes_value(true)
+                    .help("normalization rules applied to both texts, same syntax as --replacements of transcribe"),
+            )
+            .arg(
+                Arg::with_name(ARG_FORMAT)
+                    .long("format")
+                    .takes_value(true)
+                    .possible_values(&["text", "json"])
+                    .default_value("text")
+                    .help("format of the report"),
+            )
+            .arg(
+                Arg::with_name(ARG_OUTPUT_FILE)
+                    .long("output-file")
+                    .takes_value(true)
+                    .default_value(STDOUT_PATH)
+                    .help("report file path (- for stdout)"),
+            )
+    }
+
+    fn run(args: &ArgMatches) -> Result<(), String> {
+        let normalize = CleanupOptions {
+            filler: args.value_of(ARG_FILLER).unwrap().parse()?,
+            punctuation: args.value_of(ARG_PUNCTUATION).unwrap().parse()?,
+            is_normalize_width: args.is_present(ARG_NORMALIZE_WIDTH),
+            replacements: match args.value_of(ARG_RULES) {
+                Some(path) => load_replacements(path)?,
+                None => Vec::new(),
+            },
+        };
+        let load = |name: &str| -> Result<Vec<Utterance>, String> {
+            Ok(load_utterances(args.value_of(name).unwrap())?
+                .iter()
+                .map(|utterance| normalize.apply_imported(utterance))
+                .collect())
+        };
+        let reference = load(ARG_REFERENCE)?;
+        let hypothesis = load(ARG_HYPOTHESIS)?;
+
+        let report = evaluate(&reference, &hypothesis);
+        let (format, text) = if args.value_of(ARG_FORMAT) == Some("json") {
+            (OutputFormat::Json, report.to_json()?)
+        } else {
+            (OutputFormat::Text, report.to_text())
+        };
+        OutputSpec::new(format, args.value_of(ARG_OUTPUT_FILE).unwrap()).write(&text)
+    }
+}
@@ -0,0 +1,72 @@
+use crate::ami::issue_service_authorization;
+use crate::cmd_base::CmdBase;
+use crate::io::get_secret_input;
+use clap::{Arg, ArgMatches, SubCommand};
+
+const ARG_SERVICE_ID: &str = "service_id";
+const ARG_SERVICE_PASSWORD: &str = "service_password";
+const ARG_EXPIRES_IN: &str = "expires_in";
+const ENV_SERVICE_PASSWORD: &str = "ALSO_SPRACH_AMI_SERVICE_PASSWORD";
+
+pub struct IssueToken;
+
+impl CmdBase for IssueToken {
+    const NAME: &'static str = "issue-token";
+
+    fn subcommand<'a, 'b>() -> clap::App<'a, 'b> {
+        SubCommand::with_name(Self::NAME)
+            .about("issue one-time service authorization token of AmiVoice Cloud Platform")
+            .arg(
+                Arg::with_name(ARG_SERVICE_ID)
+                    .long("service-id")
+                    .required(true)
+                    .takes_value(true)
+                    .help("AmiVoice Cloud Platform service id"),
+            )
+            .arg(
+                Arg::with_name(ARG_SERVICE_PASSWORD)
+                    .long("service-password")
+                    .takes_value(true)
+                    .help("AmiVoice Cloud Platform service password (visible in the process list, prefer ALSO_SPRACH_AMI_SERVICE_PASSWORD)"),
+            )
+            .arg(
+                Arg::with_name(ARG_EXPIRES_IN)
+                    .long("expires-in")
+                    .takes_value(true)
+                    .help("validity period of the token in seconds"),
+            )
+    }
+
+    fn run(args: &ArgMatches) -> Result<(), String> {
+        let service_id = args.value_of(ARG_SERVICE_ID).unwrap();
+        let service_password = if let Some(password) = args.value_of(ARG_SERVICE_PASSWORD) {
+            password.to_string()
+        } else if let Ok(password) = std::env::var(ENV_SERVICE_PASSWORD) {
+            password.trim().to_string()
+        } else {
+            get_service_password()?
+        };
+        let expires_in_millis = match args.value_of(ARG_EXPIRES_IN) {
+            Some(v) => Some(
+                v.parse::<u64>()
+                    .map_err(|e| format!("failed to parse --expires-in: {}", e))?
+                    .checked_mul(1000)
+                    .ok_or("failed to parse --expires-in: too large")?,
+            ),
+            None => None,
+        };
+
+        let token = issue_service_authorization(service_id, &service_password, expires_in_millis)?;
+        println!("{}", token);
+        Ok(())
+    }
+}
+
+fn get_service_password() -> Result<String, String> {
+    let mut s = String::new();
+    while s.is_empty() {
+        s = get_secret_input("AmiVoice Cloud Platform Service Password: ")?;
+    }
+
+    Ok(s)
+}
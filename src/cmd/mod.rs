@@ -1,2 +1,10 @@
+pub mod cache;
+pub mod compare;
+pub mod config;
 pub mod configure;
+pub mod engines;
+pub mod eval;
+pub mod issue_token;
+pub mod render;
+pub mod render_options;
 pub mod transcribe;
@@ -0,0 +1,54 @@
+use crate::ami::JsonOutput;
+use crate::cmd::render_options;
+use crate::cmd_base::CmdBase;
+use crate::output::{check_outputs, write_outputs, OutputSpec};
+use crate::settings::Settings;
+use clap::{Arg, ArgMatches, SubCommand};
+
+const ARG_INPUT: &str = "input";
+const ARG_OUTPUT: &str = "output";
+
+pub struct Render;
+
+impl CmdBase for Render {
+    const NAME: &'static str = "render";
+
+    fn subcommand<'a, 'b>() -> clap::App<'a, 'b> {
+        SubCommand::with_name(Self::NAME)
+            .about("render saved session (json output or jsonl event log) without recognizing again")
+            .arg(
+                Arg::with_name(ARG_INPUT)
+                    .long("input")
+                    .required(true)
+                    .takes_value(true)
+                    .help("saved session file (written by --is-json-output or --format jsonl)"),
+            )
+            .arg(
+                Arg::with_name(ARG_OUTPUT)
+                    .long("output")
+                    .required(true)
+                    .takes_value(true)
+                    .multiple(true)
+                    .number_of_values(1)
+                    .value_name("format:path")
+                    .help("output as <format>:<path> (repeatable, path - for stdout). formats: text, json, jsonl, srt, vtt, ass, ttml, words, reading, hiragana, ruby, review, textgrid, eaf, audacity, ffmetadata, youtube"),
+            )
+            .args(&render_options::args())
+    }
+
+    fn run(args: &ArgMatches) -> Result<(), String> {
+        let input = args.value_of(ARG_INPUT).unwrap();
+        let outputs = args
+            .values_of(ARG_OUTPUT)
+            .unwrap()
+            .map(|spec| spec.parse())
+            .collect::<Result<Vec<OutputSpec>, String>>()?;
+
+        let settings = Settings::load()?;
+        let render_options = render_options::parse(&settings, args)?;
+
+        let output = JsonOutput::load(input)?;
+        check_outputs(&outputs)?;
+        write_outputs(&outputs, &output, &render_options)
+    }
+}
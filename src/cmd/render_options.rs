@@ -0,0 +1,203 @@
+use crate::cleanup::{load_replacements, CleanupOptions, FillerMode, PunctuationMode};
+use crate::engines;
+use crate::output::layout::LayoutOptions;
+use crate::output::RenderOptions;
+use crate::settings::Settings;
+use crate::shaping::ShapingOptions;
+use clap::{Arg, ArgMatches};
+
+const ARG_CONFIDENCE_THRESHOLD: &str = "confidence_threshold";
+const ARG_MARK_LOW_CONFIDENCE: &str = "mark_low_confidence";
+const ARG_FILLER: &str = "filler";
+const ARG_PUNCTUATION: &str = "punctuation";
+const ARG_NORMALIZE_WIDTH: &str = "normalize_width";
+const ARG_REPLACEMENTS: &str = "replacements";
+const ARG_PARAGRAPH_GAP: &str = "paragraph_gap";
+const ARG_MAX_UTTERANCE_CHARS: &str = "max_utterance_chars";
+const ARG_MAX_LINE_CHARS: &str = "max_line_chars";
+const ARG_MAX_LINES: &str = "max_lines";
+const ARG_MIN_CUE_DURATION: &str = "min_cue_duration";
+const ARG_MAX_CUE_DURATION: &str = "max_cue_duration";
+const ARG_MAX_CPS: &str = "max_cps";
+const ARG_CHAPTER_GAP: &str = "chapter_gap";
+const ARG_SUBTITLE_LANGUAGE: &str = "subtitle_language";
+
+const DEFAULT_CONFIDENCE_THRESHOLD: &str = "0.5";
+const DEFAULT_CHAPTER_GAP: &str = "5000";
+
+pub const SETTING_KEYS: &[(&str, Option<&str>)] = &[
+    (ARG_CONFIDENCE_THRESHOLD, Some(DEFAULT_CONFIDENCE_THRESHOLD)),
+    (ARG_MARK_LOW_CONFIDENCE, Some("false")),
+    (ARG_FILLER, Some("keep")),
+    (ARG_PUNCTUATION, Some("keep")),
+    (ARG_NORMALIZE_WIDTH, Some("false")),
+    (ARG_REPLACEMENTS, None),
+    (ARG_PARAGRAPH_GAP, None),
+    (ARG_MAX_UTTERANCE_CHARS, None),
+    (ARG_MAX_LINE_CHARS, None),
+    (ARG_MAX_LINES, None),
+    (ARG_MIN_CUE_DURATION, None),
+    (ARG_MAX_CUE_DURATION, None),
+    (ARG_MAX_CPS, None),
+    (ARG_CHAPTER_GAP, Some(DEFAULT_CHAPTER_GAP)),
+    (ARG_SUBTITLE_LANGUAGE, None),
+];
+
+pub fn args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        Arg::with_name(ARG_CONFIDENCE_THRESHOLD)
+            .long("confidence-threshold")
+            .takes_value(true)
+            .default_value(DEFAULT_CONFIDENCE_THRESHOLD)
+            .help("words and utterances below this confidence are reported by review format"),
+        Arg::with_name(ARG_MARK_LOW_CONFIDENCE)
+            .long("mark-low-confidence")
+            .takes_value(false)
+            .multiple(true)
+            .help("mark low confidence words inline in text, subtitle and ruby outputs (e.g. [?word?])"),
+        Arg::with_name(ARG_FILLER)
+            .long("filler")
+            .takes_value(true)
+            .possible_values(FillerMode::NAMES)
+            .default_value("keep")
+            .help("how to handle filler words such as %えー%"),
+        Arg::with_name(ARG_PUNCTUATION)
+            .long("punctuation")
+            .takes_value(true)
+            .possible_values(PunctuationMode::NAMES)
+            .default_value("keep")
+            .help("convert Japanese punctuation (、。) to ASCII or remove it"),
+        Arg::with_name(ARG_NORMALIZE_WIDTH)
+            .long("normalize-width")
+            .takes_value(false)
+            .multiple(true)
+            .help("convert full-width alphanumerics to half-width"),
+        Arg::with_name(ARG_REPLACEMENTS)
+            .long("replacements")
+            .takes_value(true)
+            .help("file of replacement rules, one <pattern> => <replacement> per line (re: prefix for regex)"),
+        Arg::with_name(ARG_PARAGRAPH_GAP)
+            .long("paragraph-gap")
+            .takes_value(true)
+            .value_name("millis")
+            .help("merge consecutive utterances of the same speaker separated by at most this gap"),
+        Arg::with_name(ARG_MAX_UTTERANCE_CHARS)
+            .long("max-utterance-chars")
+            .takes_value(true)
+            .help("split utterances longer than this at punctuation or word boundaries"),
+        Arg::with_name(ARG_MAX_LINE_CHARS)
+            .long("max-line-chars")
+            .takes_value(true)
+            .help("subtitle: max characters per line (full-width counts 1, half-width counts 0.5)"),
+        Arg::with_name(ARG_MAX_LINES)
+            .long("max-lines")
+            .takes_value(true)
+            .help("subtitle: max lines per cue"),
+        Arg::with_name(ARG_MIN_CUE_DURATION)
+            .long("min-cue-duration")
+            .takes_value(true)
+            .value_name("millis")
+            .help("subtitle: minimum duration of a cue"),
+        Arg::with_name(ARG_MAX_CUE_DURATION)
+            .long("max-cue-duration")
+            .takes_value(true)
+            .value_name("millis")
+            .help("subtitle: maximum duration of a cue"),
+        Arg::with_name(ARG_MAX_CPS)
+            .long("max-cps")
+            .takes_value(true)
+            .help("subtitle: max reading speed in characters per second"),
+        Arg::with_name(ARG_SUBTITLE_LANGUAGE)
+            .long("subtitle-language")
+            .takes_value(true)
+            .help("ttml: language of the text (xml:lang), defaults to the language of the engine"),
+        Arg::with_name(ARG_CHAPTER_GAP)
+            .long("chapter-gap")
+            .takes_value(true)
+            .value_name("millis")
+            .default_value(DEFAULT_CHAPTER_GAP)
+            .help("chapters: start a new chapter at pauses of at least this length"),
+    ]
+}
+
+pub fn parse(settings: &Settings, args: &ArgMatches) -> Result<RenderOptions, String> {
+    let confidence_threshold = settings
+        .value_of(args, ARG_CONFIDENCE_THRESHOLD)
+        .unwrap()
+        .parse::<f32>()
+        .map_err(|e| format!("failed to parse --confidence-threshold: {}", e))?;
+    if !(0.0..=1.0).contains(&confidence_threshold) {
+        return Err("--confidence-threshold must be between 0.0 and 1.0".to_string());
+    }
+
+    let replacements = match settings.value_of(args, ARG_REPLACEMENTS) {
+        Some(path) => load_replacements(&path)?,
+        None => Vec::new(),
+    };
+    let cleanup = CleanupOptions {
+        filler: settings.value_of(args, ARG_FILLER).unwrap().parse()?,
+        punctuation: settings.value_of(args, ARG_PUNCTUATION).unwrap().parse()?,
+        is_normalize_width: settings.flag_of(args, ARG_NORMALIZE_WIDTH)?,
+        replacements,
+    };
+
+    let shaping = ShapingOptions {
+        paragraph_gap: parse_optional(settings, args, ARG_PARAGRAPH_GAP, "--paragraph-gap")?,
+        max_chars: parse_optional(
+            settings,
+            args,
+            ARG_MAX_UTTERANCE_CHARS,
+            "--max-utterance-chars",
+        )?,
+    };
+
+    let layout = LayoutOptions {
+        max_line_chars: parse_optional(settings, args, ARG_MAX_LINE_CHARS, "--max-line-chars")?,
+        max_lines: parse_optional(settings, args, ARG_MAX_LINES, "--max-lines")?,
+        min_duration: parse_optional(settings, args, ARG_MIN_CUE_DURATION, "--min-cue-duration")?,
+        max_duration: parse_optional(settings, args, ARG_MAX_CUE_DURATION, "--max-cue-duration")?,
+        max_chars_per_second: parse_optional(settings, args, ARG_MAX_CPS, "--max-cps")?,
+    };
+    if matches!(layout.max_lines, Some(0)) {
+        return Err("--max-lines must be greater than 0".to_string());
+    }
+    if matches!(layout.max_line_chars, Some(max) if max < 1.0) {
+        return Err("--max-line-chars must be at least 1".to_string());
+    }
+
+    let chapter_gap = settings
+        .value_of(args, ARG_CHAPTER_GAP)
+        .unwrap()
+        .parse::<u64>()
+        .map_err(|e| format!("failed to parse --chapter-gap: {}", e))?;
+
+    Ok(RenderOptions {
+        confidence_threshold,
+        is_mark_low_confidence: settings.flag_of(args, ARG_MARK_LOW_CONFIDENCE)?,
+        cleanup,
+        shaping,
+        layout,
+        chapter_gap,
+        language: settings.value_of(args, ARG_SUBTITLE_LANGUAGE),
+        engines: engines::catalog(settings),
+    })
+}
+
+fn parse_optional<T>(
+    settings: &Settings,
+    args: &ArgMatches,
+    name: &str,
+    flag: &str,
+) -> Result<Option<T>, String>
+where
+    T: std::str::FromStr,
+    T::Err: std::fmt::Display,
+{
+    match settings.value_of(args, name) {
+        Some(v) => v
+            .parse()
+            .map(Some)
+            .map_err(|e| format!("failed to parse {}: {}", flag, e)),
+        None => Ok(None),
+    }
+}
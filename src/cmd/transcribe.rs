@@ -1,18 +1,83 @@
-use crate::ami::{AmiWebSocketClient, OutputType};
+use crate::ami::{endpoint_url, AmiWebSocketClient, OutputType, SCommandOption};
+use crate::audio::{self, TimeRange};
+use crate::cache;
+use crate::cmd::configure::ENV_API_KEY;
+use crate::cmd::render_options;
 use crate::cmd_base::CmdBase;
+use crate::engines::validate_grammar_file_names;
 use crate::fs::load_api_key;
-use crate::io::get_input;
+use crate::io::{get_secret_input, is_stdin_tty};
+use crate::output::{check_outputs, write_outputs, OutputFormat, OutputSpec};
+use crate::pacing::{self, Pacing, PacingMode};
+use crate::session::{self, SessionOptions};
+use crate::settings::Settings;
+use crate::timeout::{self, parse_timeout, Timeouts};
 use clap::{Arg, ArgMatches, SubCommand};
 
 const ARG_AUDIO_FILE: &str = "audio_file";
-const ARG_API_KEY: &str = "api_key";
+pub const ARG_API_KEY: &str = "api_key";
+pub const ARG_AUTHORIZATION_TOKEN: &str = "authorization_token";
 const ARG_AUDIO_FORMAT: &str = "audio_format";
 const ARG_NO_LOG: &str = "no_log";
 const ARG_GRAMMAR_FILE_NAMES: &str = "grammar_file_names";
 const ARG_OUTPUT_FILE: &str = "output_file";
+const ARG_OUTPUT: &str = "output";
 const ARG_VERBOSE: &str = "verbose";
 const ARG_TRACE: &str = "trace";
 const ARG_OUTPUT_JSON: &str = "output_json";
+const ARG_FORMAT: &str = "format";
+const ARG_NO_CONFIG: &str = "no_config";
+const ARG_SPEAKER_DIARIZATION: &str = "speaker_diarization";
+const ARG_NO_CACHE: &str = "no_cache";
+const ARG_CACHE_MAX_SIZE: &str = "cache_max_size";
+const ARG_START: &str = "start";
+const ARG_END: &str = "end";
+const ARG_DURATION: &str = "duration";
+const ARG_SPLIT_CHANNELS: &str = "split_channels";
+const ARG_CHANNEL_LABELS: &str = "channel_labels";
+const ARG_PACING: &str = "pacing";
+const ARG_CHUNK_SIZE: &str = "chunk_size";
+const ARG_CONNECT_TIMEOUT: &str = "connect_timeout";
+const ARG_INIT_TIMEOUT: &str = "init_timeout";
+const ARG_IDLE_TIMEOUT: &str = "idle_timeout";
+const ARG_SESSION_TIMEOUT: &str = "session_timeout";
+pub const ARG_ALLOW_UNKNOWN_ENGINE: &str = "allow_unknown_engine";
+
+const DEFAULT_AUDIO_FORMAT: &str = "16k";
+const DEFAULT_GRAMMAR_FILE_NAMES: &str = "-a-general";
+const DEFAULT_PACING: &str = "fixed";
+
+const SETTING_KEYS: &[(&str, Option<&str>)] = &[
+    (ARG_OUTPUT_FILE, None),
+    (ARG_OUTPUT, None),
+    (ARG_AUDIO_FORMAT, Some(DEFAULT_AUDIO_FORMAT)),
+    (ARG_GRAMMAR_FILE_NAMES, Some(DEFAULT_GRAMMAR_FILE_NAMES)),
+    (ARG_ALLOW_UNKNOWN_ENGINE, Some("false")),
+    (ARG_NO_LOG, Some("false")),
+    (ARG_SPEAKER_DIARIZATION, Some("false")),
+    (ARG_SPLIT_CHANNELS, Some("false")),
+    (ARG_CHANNEL_LABELS, None),
+    (ARG_PACING, Some(DEFAULT_PACING)),
+    (ARG_CHUNK_SIZE, Some(pacing::DEFAULT_CHUNK_SIZE)),
+    (ARG_CONNECT_TIMEOUT, Some(timeout::DEFAULT_CONNECT_TIMEOUT)),
+    (ARG_INIT_TIMEOUT, Some(timeout::DEFAULT_INIT_TIMEOUT)),
+    (ARG_IDLE_TIMEOUT, Some(timeout::DEFAULT_IDLE_TIMEOUT)),
+    (ARG_SESSION_TIMEOUT, Some(timeout::DEFAULT_SESSION_TIMEOUT)),
+    (ARG_OUTPUT_JSON, Some("false")),
+    (ARG_FORMAT, None),
+    (ARG_NO_CACHE, Some("false")),
+    (ARG_CACHE_MAX_SIZE, Some(cache::DEFAULT_MAX_SIZE)),
+    (ARG_VERBOSE, Some("false")),
+    (ARG_TRACE, Some("false")),
+];
+
+pub fn setting_keys() -> Vec<(&'static str, Option<&'static str>)> {
+    SETTING_KEYS
+        .iter()
+        .chain(render_options::SETTING_KEYS.iter())
+        .cloned()
+        .collect()
+}
 
 pub struct Transcribe;
 
@@ -27,14 +92,46 @@ impl CmdBase for Transcribe {
                     .long("audio-path")
                     .required(true)
                     .takes_value(true)
-                    .help("target audio file path")
+                    .multiple(true)
+                    .number_of_values(1)
+                    .help("target audio file path (repeatable: the files are streamed back to back in one session)")
+            )
+            .arg(
+                Arg::with_name(ARG_START)
+                    .long("start")
+                    .takes_value(true)
+                    .value_name("time")
+                    .help("transcribe from this position (seconds or [hh:]mm:ss[.fff], PCM or WAV audio only)")
+            )
+            .arg(
+                Arg::with_name(ARG_END)
+                    .long("end")
+                    .takes_value(true)
+                    .value_name("time")
+                    .help("transcribe until this position (seconds or [hh:]mm:ss[.fff])")
+            )
+            .arg(
+                Arg::with_name(ARG_DURATION)
+                    .long("duration")
+                    .takes_value(true)
+                    .value_name("time")
+                    .conflicts_with(ARG_END)
+                    .help("transcribe this length from --start (seconds or [hh:]mm:ss[.fff])")
             )
             .arg(
                 Arg::with_name(ARG_OUTPUT_FILE)
                     .long("output-file")
-                    .required(true)
                     .takes_value(true)
-                    .help("output file path")
+                    .help("output file path (- for stdout)")
+            )
+            .arg(
+                Arg::with_name(ARG_OUTPUT)
+                    .long("output")
+                    .takes_value(true)
+                    .multiple(true)
+                    .number_of_values(1)
+                    .value_name("format:path")
+                    .help("additional output as <format>:<path> (repeatable, path - for stdout). formats: text, json, jsonl, srt, vtt, ass, ttml, words, reading, hiragana, ruby, review, textgrid, eaf, audacity, ffmetadata, youtube")
             )
             .arg(
                 Arg::with_name(ARG_API_KEY)
@@ -42,11 +139,19 @@ impl CmdBase for Transcribe {
                     .takes_value(true)
                     .help("AmiVoice Cloud Platform API KEY")
             )
+            .arg(
+                Arg::with_name(ARG_AUTHORIZATION_TOKEN)
+                    .long("authorization-token")
+                    .takes_value(true)
+                    .conflicts_with(ARG_API_KEY)
+                    .help("one-time service authorization token (instead of API KEY)")
+            )
             .arg(
                 Arg::with_name(ARG_AUDIO_FORMAT)
                     .long("audio-foramt")
+                    .visible_alias("audio-format")
                     .takes_value(true)
-                    .default_value("16k")
+                    .default_value(DEFAULT_AUDIO_FORMAT)
                     .help("audio file format")
                     .help("audio file foramt. Details: https://acp.amivoice.com/main/manual/%e9%9f%b3%e5%a3%b0%e3%83%95%e3%82%a9%e3%83%bc%e3%83%9e%e3%83%83%e3%83%88%e3%81%ab%e3%81%a4%e3%81%84%e3%81%a6/")
             )
@@ -54,8 +159,16 @@ impl CmdBase for Transcribe {
                 Arg::with_name(ARG_GRAMMAR_FILE_NAMES)
                     .long("grammar-file-names")
                     .takes_value(true)
-                    .default_value("-a-general")
-                    .help("Types of Speech Recognition Engines")
+                    .default_value(DEFAULT_GRAMMAR_FILE_NAMES)
+                    .allow_hyphen_values(true)
+                    .help("Types of Speech Recognition Engines (see engines subcommand)")
+            )
+            .arg(
+                Arg::with_name(ARG_ALLOW_UNKNOWN_ENGINE)
+                    .long("allow-unknown-engine")
+                    .takes_value(false)
+                    .multiple(true)
+                    .help("use --grammar-file-names even if it is not in the engine catalog")
             )
             .arg(
                 Arg::with_name(ARG_NO_LOG)
@@ -64,6 +177,27 @@ impl CmdBase for Transcribe {
                     .multiple(true)
                     .help("flag of saving audio file and recognition result")
             )
+            .arg(
+                Arg::with_name(ARG_SPEAKER_DIARIZATION)
+                    .long("speaker-diarization")
+                    .takes_value(false)
+                    .multiple(true)
+                    .help("enable speaker diarization (speaker labels are added to words and utterances)")
+            )
+            .arg(
+                Arg::with_name(ARG_SPLIT_CHANNELS)
+                    .long("split-channels")
+                    .takes_value(false)
+                    .multiple(true)
+                    .help("transcribe every channel of a 16 bit PCM WAV file in its own session and merge the results with the channel as speaker")
+            )
+            .arg(
+                Arg::with_name(ARG_CHANNEL_LABELS)
+                    .long("channel-labels")
+                    .takes_value(true)
+                    .value_name("label,...")
+                    .help("speaker labels of the channels with --split-channels (default: ch1,ch2,...)")
+            )
             .arg(
                 Arg::with_name(ARG_VERBOSE)
                     .long("verbose")
@@ -84,48 +218,289 @@ impl CmdBase for Transcribe {
                     .multiple(true)
                     .help("flag of output json")
             )
+            .arg(
+                Arg::with_name(ARG_FORMAT)
+                    .long("format")
+                    .takes_value(true)
+                    .possible_values(OutputFormat::NAMES)
+                    .help("format of --output-file (overrides --is-json-output). jsonl streams every received event as it arrives")
+            )
+            .arg(
+                Arg::with_name(ARG_NO_CACHE)
+                    .long("no-cache")
+                    .takes_value(false)
+                    .multiple(true)
+                    .help("always recognize audio without using cached result")
+            )
+            .arg(
+                Arg::with_name(ARG_CACHE_MAX_SIZE)
+                    .long("cache-max-size")
+                    .takes_value(true)
+                    .default_value(cache::DEFAULT_MAX_SIZE)
+                    .help("total size limit of cached results (e.g. 512M, 2G)")
+            )
+            .arg(
+                Arg::with_name(ARG_PACING)
+                    .long("pacing")
+                    .takes_value(true)
+                    .possible_values(PacingMode::NAMES)
+                    .default_value(DEFAULT_PACING)
+                    .help("how fast audio is sent: a short pause after every chunk, at the real-time rate of the audio (PCM or WAV), or as fast as the server accepts")
+            )
+            .arg(
+                Arg::with_name(ARG_CHUNK_SIZE)
+                    .long("chunk-size")
+                    .takes_value(true)
+                    .value_name("bytes")
+                    .default_value(pacing::DEFAULT_CHUNK_SIZE)
+                    .help("size of the audio data sent in one message")
+            )
+            .arg(
+                Arg::with_name(ARG_CONNECT_TIMEOUT)
+                    .long("connect-timeout")
+                    .takes_value(true)
+                    .value_name("time")
+                    .default_value(timeout::DEFAULT_CONNECT_TIMEOUT)
+                    .help("give up connecting to the server after this time (seconds or [hh:]mm:ss, 0 for no limit)")
+            )
+            .arg(
+                Arg::with_name(ARG_INIT_TIMEOUT)
+                    .long("init-timeout")
+                    .takes_value(true)
+                    .value_name("time")
+                    .default_value(timeout::DEFAULT_INIT_TIMEOUT)
+                    .help("give up when the server does not answer the s command within this time (0 for no limit)")
+            )
+            .arg(
+                Arg::with_name(ARG_IDLE_TIMEOUT)
+                    .long("idle-timeout")
+                    .takes_value(true)
+                    .value_name("time")
+                    .default_value(timeout::DEFAULT_IDLE_TIMEOUT)
+                    .help("give up when the server neither sends events nor accepts audio for this time (0 for no limit)")
+            )
+            .arg(
+                Arg::with_name(ARG_SESSION_TIMEOUT)
+                    .long("session-timeout")
+                    .takes_value(true)
+                    .value_name("time")
+                    .default_value(timeout::DEFAULT_SESSION_TIMEOUT)
+                    .help("give up when the whole session takes longer than this time (0 for no limit)")
+            )
+            .arg(
+                Arg::with_name(ARG_NO_CONFIG)
+                    .long("no-config")
+                    .takes_value(false)
+                    .help("ignore defaults and engines in global and project config files")
+            )
+            .args(&render_options::args())
     }
 
     fn run(args: &ArgMatches) -> Result<(), String> {
-        let api_key = if let Some(api_key) = args.value_of(ARG_API_KEY) {
-            api_key.to_string()
-        } else if let Some(api_key) = load_api_key()? {
-            api_key
+        let settings = if args.is_present(ARG_NO_CONFIG) {
+            Settings::default()
         } else {
-            get_api_key()?
+            Settings::load()?
         };
+        settings.check_keys(&setting_keys())?;
 
-        let audio_file_path = args.value_of(ARG_AUDIO_FILE).unwrap().to_string();
-        let output_file_path = args.value_of(ARG_OUTPUT_FILE).unwrap();
-        let audio_format = args.value_of(ARG_AUDIO_FORMAT).unwrap().to_string();
-        let grammar_file_names = args.value_of(ARG_GRAMMAR_FILE_NAMES).unwrap().to_string();
+        let audio_file_paths: Vec<String> = args
+            .values_of(ARG_AUDIO_FILE)
+            .unwrap()
+            .map(|path| path.to_string())
+            .collect();
+        let audio_file_path = audio_file_paths[0].clone();
+        let range = TimeRange::parse(
+            args.value_of(ARG_START),
+            args.value_of(ARG_END),
+            args.value_of(ARG_DURATION),
+        )?;
+        if audio_file_paths.len() > 1 && !range.is_full() {
+            return Err(
+                "--start, --end and --duration cannot be used with several audio files".to_string(),
+            );
+        }
+        let audio_format = settings.value_of(args, ARG_AUDIO_FORMAT).unwrap();
+        let grammar_file_names = settings.value_of(args, ARG_GRAMMAR_FILE_NAMES).unwrap();
+        if !settings.flag_of(args, ARG_ALLOW_UNKNOWN_ENGINE)? {
+            validate_grammar_file_names(&settings, &grammar_file_names)?;
+        }
 
-        let is_no_log = args.occurrences_of(ARG_NO_LOG) > 0;
-        let is_verbose = args.flag_of(ARG_VERBOSE);
-        let is_trace = args.flag_of(ARG_TRACE);
-        let is_output_json = args.flag_of(ARG_OUTPUT_JSON);
+        let is_no_log = settings.flag_of(args, ARG_NO_LOG)?;
+        let is_verbose = settings.flag_of(args, ARG_VERBOSE)?;
+        let is_trace = settings.flag_of(args, ARG_TRACE)?;
+        let is_output_json = settings.flag_of(args, ARG_OUTPUT_JSON)?;
+        let is_no_cache = settings.flag_of(args, ARG_NO_CACHE)?;
+        let cache_max_size =
+            cache::parse_size(&settings.value_of(args, ARG_CACHE_MAX_SIZE).unwrap())?;
 
         let output_type = resolve_output_type(is_verbose, is_trace);
 
-        let mut client = AmiWebSocketClient::new(
-            api_key,
+        let mut outputs = Vec::new();
+        if let Some(path) = settings.value_of(args, ARG_OUTPUT_FILE) {
+            let format = if let Some(format) = settings.value_of(args, ARG_FORMAT) {
+                format.parse()?
+            } else if is_output_json {
+                OutputFormat::Json
+            } else {
+                OutputFormat::Text
+            };
+            outputs.push(OutputSpec::new(format, &path));
+        }
+        for spec in settings.values_of(args, ARG_OUTPUT) {
+            outputs.push(spec.parse()?);
+        }
+        check_outputs(&outputs)?;
+        let render_options = render_options::parse(&settings, args)?;
+        let mut pacing = Pacing {
+            mode: settings.value_of(args, ARG_PACING).unwrap().parse()?,
+            chunk_size: pacing::parse_chunk_size(
+                &settings.value_of(args, ARG_CHUNK_SIZE).unwrap(),
+            )?,
+            ..Pacing::default()
+        };
+        let timeout_of = |name, flag| parse_timeout(flag, &settings.value_of(args, name).unwrap());
+        let timeouts = Timeouts {
+            connect: timeout_of(ARG_CONNECT_TIMEOUT, "connect-timeout")?,
+            initialize: timeout_of(ARG_INIT_TIMEOUT, "init-timeout")?,
+            idle: timeout_of(ARG_IDLE_TIMEOUT, "idle-timeout")?,
+            session: timeout_of(ARG_SESSION_TIMEOUT, "session-timeout")?,
+        };
+
+        let mut option = SCommandOption {
             audio_format,
             grammar_file_names,
-            !is_no_log,
-            is_output_json,
-            audio_file_path,
-            output_file_path,
-            output_type,
-        )?;
+            authorization: String::new(),
+            is_speaker_diarization: settings.flag_of(args, ARG_SPEAKER_DIARIZATION)?,
+        };
+        if settings.flag_of(args, ARG_SPLIT_CHANNELS)? {
+            if audio_file_paths.len() > 1 {
+                return Err("--split-channels cannot be used with several audio files".to_string());
+            }
+            // progress of concurrent sessions would be mixed up
+            if is_verbose || is_trace {
+                return Err("-v and --trace cannot be used with --split-channels".to_string());
+            }
+            let labels: Vec<String> = match settings.value_of(args, ARG_CHANNEL_LABELS) {
+                Some(labels) => labels.split(',').map(|s| s.trim().to_string()).collect(),
+                None => Vec::new(),
+            };
+            option.authorization = resolve_authorization(args)?;
+            let options = SessionOptions {
+                is_with_log: !is_no_log,
+                is_no_cache,
+                pacing,
+                timeouts,
+            };
+            let (streams, outputs): (Vec<OutputSpec>, Vec<OutputSpec>) =
+                outputs.into_iter().partition(OutputSpec::is_streaming);
+            let streams = streams
+                .iter()
+                .map(OutputSpec::open_stream)
+                .collect::<Result<Vec<_>, String>>()?;
+            let output = session::run_channels(
+                option,
+                &options,
+                &audio_file_path,
+                &range,
+                &labels,
+                streams,
+            )?;
+            write_outputs(&outputs, &output, &render_options)?;
+            if !is_no_cache {
+                cache::warn_on_error(cache::prune(Some(cache_max_size), None));
+            }
+            return match output.error_message() {
+                Some(error) => Err(error.clone()),
+                None => Ok(()),
+            };
+        }
+
+        let endpoint = endpoint_url(!is_no_log);
+
+        let cache_key = if is_no_cache {
+            None
+        } else {
+            let variant = range.cache_key();
+            let key = cache::compute_key(&audio_file_paths, &option, &endpoint, &variant)?;
+            if let Some(entry) = cache::load(&key)? {
+                eprintln!("use cached result ({})", &key[..12]);
+                return write_outputs(&outputs, &entry.output, &render_options);
+            }
+            Some(key)
+        };
+
+        if pacing.mode == PacingMode::Realtime {
+            let (bytes_per_second, header_size) =
+                audio::byte_rate(&audio_file_path, &option.audio_format)?;
+            pacing.bytes_per_second = bytes_per_second;
+            pacing.header_size = header_size;
+        }
+        let (audio, files) = if audio_file_paths.len() > 1 {
+            audio::open_files(&audio_file_paths, &option.audio_format)?
+        } else {
+            let audio = audio::open(&audio_file_path, &option.audio_format, &range)?;
+            (audio, Vec::new())
+        };
+        option.authorization = resolve_authorization(args)?;
+
+        let mut client = AmiWebSocketClient::new(option, !is_no_log, audio, output_type, timeouts)?;
+        client.set_time_offset(range.start);
+        client.set_source_files(files);
+        client.set_pacing(pacing);
+
+        let (streams, outputs): (Vec<OutputSpec>, Vec<OutputSpec>) =
+            outputs.into_iter().partition(OutputSpec::is_streaming);
+        for spec in &streams {
+            client.add_event_sink(spec.open_stream()?);
+        }
+
+        let result = client.exec();
+        write_outputs(&outputs, client.output(), &render_options)?;
 
-        client.exec()
+        if let Some(key) = cache_key {
+            if client.output().is_succeeded() {
+                cache::warn_on_error(
+                    cache::store(
+                        &key,
+                        &audio_file_paths.join(", "),
+                        &endpoint,
+                        client.output(),
+                    )
+                    .and_then(|_| cache::prune(Some(cache_max_size), None)),
+                );
+            }
+        }
+        result
+    }
+}
+
+// --authorization-token, --api-key, environment variable, configured key, then prompt
+pub fn resolve_authorization(args: &ArgMatches) -> Result<String, String> {
+    if let Some(token) = args.value_of(ARG_AUTHORIZATION_TOKEN) {
+        Ok(token.to_string())
+    } else if let Some(api_key) = args.value_of(ARG_API_KEY) {
+        Ok(api_key.to_string())
+    } else if let Ok(api_key) = std::env::var(ENV_API_KEY) {
+        Ok(api_key.trim().to_string())
+    } else if let Some(api_key) = load_api_key()? {
+        Ok(api_key)
+    } else {
+        get_api_key()
     }
 }
 
 fn get_api_key() -> Result<String, String> {
+    if !is_stdin_tty() {
+        return Err(format!(
+            "API KEY is not configured and stdin is not a terminal. use --api-key, {} or configure",
+            ENV_API_KEY
+        ));
+    }
+
     let mut s;
     while {
-        s = get_input("AmiVoice Cloud Platform API KEY: ")?;
+        s = get_secret_input("AmiVoice Cloud Platform API KEY: ")?;
 
         s.is_empty()
     } {}
@@ -133,16 +508,6 @@ fn get_api_key() -> Result<String, String> {
     Ok(s)
 }
 
-trait ArgMachesExt {
-    fn flag_of(&self, name: &str) -> bool;
-}
-
-impl<'a> ArgMachesExt for ArgMatches<'a> {
-    fn flag_of(&self, name: &str) -> bool {
-        self.occurrences_of(name) > 0
-    }
-}
-
 fn resolve_output_type(is_verbose: bool, is_trace: bool) -> OutputType {
     if is_trace {
         OutputType::Trace
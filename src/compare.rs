@@ -0,0 +1,204 @@
+use crate::eval::Score;
+use crate::output::format_clock;
+use crate::transcript::{Token, Transcript, Utterance};
+use serde::Serialize;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EngineSpec {
+    pub label: String,
+    pub grammar_file_names: String,
+    pub audio_format: Option<String>,
+    pub is_speaker_diarization: bool,
+}
+
+impl FromStr for EngineSpec {
+    type Err = String;
+
+    // <grammar_file_names>[,format=<audio_format>][,diarization]
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split(',');
+        let grammar_file_names = parts.next().unwrap_or("").trim().to_string();
+        if grammar_file_names.is_empty() {
+            return Err(format!(
+                "failed to parse engine \"{}\": grammar file names is empty",
+                s
+            ));
+        }
+        let mut spec = EngineSpec {
+            label: s.to_string(),
+            grammar_file_names,
+            audio_format: None,
+            is_speaker_diarization: false,
+        };
+        for part in parts.map(str::trim) {
+            if part == "diarization" || part == "speaker-diarization" {
+                spec.is_speaker_diarization = true;
+            } else if let Some(format) = part.strip_prefix("format=") {
+                if format.is_empty() {
+                    return Err(format!(
+                        "failed to parse engine \"{}\": audio format is empty",
+                        s
+                    ));
+                }
+                spec.audio_format = Some(format.to_string());
+            } else {
+                return Err(format!(
+                    "failed to parse engine \"{}\": unknown parameter \"{}\" (expected format=<audio format> or diarization)",
+                    s, part
+                ));
+            }
+        }
+        Ok(spec)
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct EngineResult {
+    pub label: String,
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cer: Option<Score>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wer: Option<Score>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Cell {
+    pub text: String,
+    pub confidence: f32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Row {
+    pub starttime: u64,
+    pub endtime: u64,
+    pub is_different: bool,
+    pub results: Vec<Cell>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CompareReport {
+    pub engines: Vec<EngineResult>,
+    pub rows: Vec<Row>,
+}
+
+// engines segment utterances differently, so results are aligned by time:
+// each utterance of the first transcript opens a row and every token goes to the row in which its midpoint falls.
+pub fn align_by_time(transcripts: &[Transcript]) -> Vec<Row> {
+    let starts: Vec<u64> = match transcripts.first() {
+        Some(base) => base.utterances.iter().map(|u| u.starttime).collect(),
+        None => return Vec::new(),
+    };
+    let count = starts.len().max(1);
+    let mut cells: Vec<Vec<Vec<Token>>> = vec![vec![Vec::new(); transcripts.len()]; count];
+    for (column, transcript) in transcripts.iter().enumerate() {
+        for token in transcript.utterances.iter().flat_map(|u| u.tokens.iter()) {
+            let middle = (token.starttime + token.endtime) / 2;
+            let row = starts.iter().rposition(|&s| s <= middle).unwrap_or(0);
+            cells[row][column].push(token.clone());
+        }
+    }
+
+    cells
+        .into_iter()
+        .filter(|row| row.iter().any(|tokens| !tokens.is_empty()))
+        .map(|row| {
+            let utterances: Vec<Utterance> = row.into_iter().map(Utterance::from_tokens).collect();
+            let filled = || utterances.iter().filter(|u| !u.tokens.is_empty());
+            let compact = |u: &Utterance| -> String {
+                u.text.chars().filter(|c| !c.is_whitespace()).collect()
+            };
+            let first = compact(&utterances[0]);
+            Row {
+                starttime: filled().map(|u| u.starttime).min().unwrap_or(0),
+                endtime: filled().map(|u| u.endtime).max().unwrap_or(0),
+                is_different: utterances.iter().any(|u| compact(u) != first),
+                results: utterances
+                    .iter()
+                    .map(|u| Cell {
+                        text: u.text.clone(),
+                        confidence: u.confidence,
+                    })
+                    .collect(),
+            }
+        })
+        .collect()
+}
+
+impl CompareReport {
+    pub fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string_pretty(self)
+            .map_err(|e| format!("failed to serialize compare report: {}", e))
+    }
+
+    pub fn to_text(&self, is_only_differences: bool) -> String {
+        let mut s = String::new();
+        for (index, engine) in self.engines.iter().enumerate() {
+            s.push_str(&format!("[{}] {}", index + 1, engine.label));
+            if let Some(error) = &engine.error {
+                s.push_str(&format!(" (error: {})", error));
+            }
+            if let (Some(cer), Some(wer)) = (&engine.cer, &engine.wer) {
+                s.push_str(&format!(
+                    "\n    CER {:.2}% WER {:.2}%",
+                    cer.rate * 100.0,
+                    wer.rate * 100.0
+                ));
+            }
+            s.push('\n');
+        }
+
+        for (index, row) in self.rows.iter().enumerate() {
+            if is_only_differences && !row.is_different {
+                continue;
+            }
+            s.push_str(&format!(
+                "\n#{} {} - {}{}\n",
+                index + 1,
+                format_clock(row.starttime),
+                format_clock(row.endtime),
+                if row.is_different { " *" } else { "" }
+            ));
+            for (column, cell) in row.results.iter().enumerate() {
+                s.push_str(&format!(
+                    "  [{}] {:.2} {}\n",
+                    column + 1,
+                    cell.confidence,
+                    cell.text
+                ));
+            }
+        }
+        s
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn engine_spec_parses_parameters() {
+        let spec: EngineSpec = "-a-general, format=8k ,diarization".parse().unwrap();
+        assert_eq!(spec.label, "-a-general, format=8k ,diarization");
+        assert_eq!(spec.grammar_file_names, "-a-general");
+        assert_eq!(spec.audio_format.as_deref(), Some("8k"));
+        assert!(spec.is_speaker_diarization);
+
+        let spec: EngineSpec = "-a-medgeneral".parse().unwrap();
+        assert_eq!(spec.grammar_file_names, "-a-medgeneral");
+        assert_eq!(spec.audio_format, None);
+        assert!(!spec.is_speaker_diarization);
+
+        let spec: EngineSpec = "-a-general,speaker-diarization".parse().unwrap();
+        assert!(spec.is_speaker_diarization);
+    }
+
+    #[test]
+    fn engine_spec_rejects_invalid() {
+        assert!("".parse::<EngineSpec>().is_err());
+        assert!(" ,format=8k".parse::<EngineSpec>().is_err());
+        assert!("-a-general,format=".parse::<EngineSpec>().is_err());
+        assert!("-a-general,fast".parse::<EngineSpec>().is_err());
+    }
+}
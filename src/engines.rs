@@ -0,0 +1,108 @@
+use crate::settings::Settings;
+use serde::{Deserialize, Serialize};
+
+const LOGGING_VARIANTS: &[&str] = &["log", "nolog"];
+
+// name, language, domain, sample rates
+const BUILTIN_ENGINES: &[(&str, &str, &str, &[&str])] = &[
+    ("-a-general", "ja", "general", &["8k", "16k"]),
+    ("-a-general-en", "en", "general", &["16k"]),
+    ("-a-general-zh", "zh", "general", &["16k"]),
+    ("-a-general-ko", "ko", "general", &["16k"]),
+    ("-a-medgeneral", "ja", "medical", &["8k", "16k"]),
+    ("-a-bizmrreport", "ja", "pharmaceutical", &["8k", "16k"]),
+    ("-a-bizfinance", "ja", "finance", &["8k", "16k"]),
+    ("-a-bizinsurance", "ja", "insurance", &["8k", "16k"]),
+    ("-a-medkarte", "ja", "electronic medical record", &["16k"]),
+];
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Engine {
+    pub name: String,
+    #[serde(default)]
+    pub language: String,
+    #[serde(default)]
+    pub domain: String,
+    #[serde(default)]
+    pub sample_rates: Vec<String>,
+    #[serde(default = "default_logging")]
+    pub logging: Vec<String>,
+    #[serde(skip)]
+    pub is_custom: bool,
+}
+
+fn default_logging() -> Vec<String> {
+    LOGGING_VARIANTS.iter().map(|s| s.to_string()).collect()
+}
+
+// built-in engines followed by the engines in the project and global configs.
+// a configured engine with the same name as a built-in one replaces it.
+pub fn catalog(settings: &Settings) -> Vec<Engine> {
+    let mut engines: Vec<Engine> = BUILTIN_ENGINES
+        .iter()
+        .map(|(name, language, domain, sample_rates)| Engine {
+            name: name.to_string(),
+            language: language.to_string(),
+            domain: domain.to_string(),
+            sample_rates: sample_rates.iter().map(|s| s.to_string()).collect(),
+            logging: default_logging(),
+            is_custom: false,
+        })
+        .collect();
+    for mut engine in settings.engines() {
+        engine.is_custom = true;
+        match engines.iter_mut().find(|e| e.name == engine.name) {
+            Some(e) => *e = engine,
+            None => engines.push(engine),
+        }
+    }
+    engines
+}
+
+fn distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut row = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            row[j + 1] = (prev[j] + if ca == *cb { 0 } else { 1 })
+                .min(prev[j + 1] + 1)
+                .min(row[j] + 1);
+        }
+        prev = row;
+    }
+    prev[b.len()]
+}
+
+pub fn suggest<'a>(name: &str, engines: &'a [Engine]) -> Option<&'a Engine> {
+    engines
+        .iter()
+        .map(|engine| (distance(name, &engine.name), engine))
+        .filter(|(d, engine)| *d <= engine.name.chars().count() / 3)
+        .min_by_key(|(d, _)| *d)
+        .map(|(_, engine)| engine)
+}
+
+// grammar file names may hold several names separated by spaces
+pub fn validate_grammar_file_names(
+    settings: &Settings,
+    grammar_file_names: &str,
+) -> Result<(), String> {
+    let engines = catalog(settings);
+    for name in grammar_file_names.split_whitespace() {
+        if engines.iter().any(|engine| engine.name == name) {
+            continue;
+        }
+        let hint = match suggest(name, &engines) {
+            Some(engine) => format!(" did you mean \"{}\"?", engine.name),
+            None => String::new(),
+        };
+        return Err(format!(
+            "unknown engine \"{}\".{} run `{} engines` to list known engines, add it to \"engines\" in the config, or pass --allow-unknown-engine",
+            name,
+            hint,
+            crate_name!()
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,573 @@
+use crate::transcript::Utterance;
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Edit {
+    Equal,
+    Substitute,
+    Delete,
+    Insert,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct Score {
+    pub substitutions: usize,
+    pub deletions: usize,
+    pub insertions: usize,
+    pub reference_length: usize,
+    pub rate: f64,
+}
+
+impl Score {
+    fn add(&mut self, edit: Edit) {
+        match edit {
+            Edit::Equal => self.reference_length += 1,
+            Edit::Substitute => {
+                self.substitutions += 1;
+                self.reference_length += 1;
+            }
+            Edit::Delete => {
+                self.deletions += 1;
+                self.reference_length += 1;
+            }
+            Edit::Insert => self.insertions += 1,
+        }
+        self.rate = if self.reference_length == 0 {
+            if self.errors() == 0 {
+                0.0
+            } else {
+                1.0
+            }
+        } else {
+            self.errors() as f64 / self.reference_length as f64
+        };
+    }
+
+    pub fn errors(&self) -> usize {
+        self.substitutions + self.deletions + self.insertions
+    }
+
+    fn summary(&self) -> String {
+        format!(
+            "{:.2}% (S {} D {} I {} / N {})",
+            self.rate * 100.0,
+            self.substitutions,
+            self.deletions,
+            self.insertions,
+            self.reference_length
+        )
+    }
+}
+
+// what WER counts as a word
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WordUnit {
+    Token,
+    CharacterType,
+}
+
+impl WordUnit {
+    fn description(self) -> &'static str {
+        match self {
+            WordUnit::Token => "recognizer tokens",
+            WordUnit::CharacterType => "character type runs",
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct UtteranceReport {
+    pub index: usize,
+    pub reference: String,
+    pub hypothesis: String,
+    pub cer: Score,
+    pub wer: Score,
+    pub diff: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Report {
+    pub cer: Score,
+    pub wer: Score,
+    pub word_unit: WordUnit,
+    pub utterances: Vec<UtteranceReport>,
+}
+
+// alignments up to this many DP cells keep the whole backtrace table
+const TABLE_CELLS: usize = 1 << 16;
+
+// levenshtein alignment with backtrace. returns (edit, reference index, hypothesis index) in order.
+fn align_table<T: PartialEq>(reference: &[T], hypothesis: &[T]) -> Vec<(Edit, usize, usize)> {
+    let n = reference.len();
+    let m = hypothesis.len();
+    let mut edits = vec![Edit::Equal; (n + 1) * (m + 1)];
+    let mut prev: Vec<usize> = (0..=m).collect();
+    let mut row = vec![0; m + 1];
+    for edit in edits.iter_mut().take(m + 1).skip(1) {
+        *edit = Edit::Insert;
+    }
+    for i in 1..=n {
+        row[0] = i;
+        edits[i * (m + 1)] = Edit::Delete;
+        for j in 1..=m {
+            let is_equal = reference[i - 1] == hypothesis[j - 1];
+            let diagonal = prev[j - 1] + if is_equal { 0 } else { 1 };
+            let delete = prev[j] + 1;
+            let insert = row[j - 1] + 1;
+            let (cost, edit) = if diagonal <= delete && diagonal <= insert {
+                (
+                    diagonal,
+                    if is_equal {
+                        Edit::Equal
+                    } else {
+                        Edit::Substitute
+                    },
+                )
+            } else if delete <= insert {
+                (delete, Edit::Delete)
+            } else {
+                (insert, Edit::Insert)
+            };
+            row[j] = cost;
+            edits[i * (m + 1) + j] = edit;
+        }
+        std::mem::swap(&mut prev, &mut row);
+    }
+
+    let mut path = Vec::new();
+    let (mut i, mut j) = (n, m);
+    while i > 0 || j > 0 {
+        let edit = edits[i * (m + 1) + j];
+        match edit {
+            Edit::Equal | Edit::Substitute => {
+                i -= 1;
+                j -= 1;
+            }
+            Edit::Delete => i -= 1,
+            Edit::Insert => j -= 1,
+        }
+        path.push((edit, i, j));
+    }
+    path.reverse();
+    path
+}
+
+// edit distances from the whole reference to every prefix of the hypothesis, one row at a time
+fn last_row<T: PartialEq>(reference: &[T], hypothesis: &[T]) -> Vec<usize> {
+    let mut prev: Vec<usize> = (0..=hypothesis.len()).collect();
+    let mut row = vec![0; hypothesis.len() + 1];
+    for (i, r) in reference.iter().enumerate() {
+        row[0] = i + 1;
+        for (j, h) in hypothesis.iter().enumerate() {
+            let diagonal = prev[j] + if r == h { 0 } else { 1 };
+            row[j + 1] = diagonal.min(prev[j + 1] + 1).min(row[j] + 1);
+        }
+        std::mem::swap(&mut prev, &mut row);
+    }
+    prev
+}
+
+// Hirschberg's algorithm: the reference is split in half at the hypothesis position where an optimal
+// path crosses, so memory stays linear in the input length for long transcripts.
+fn align_into<T: PartialEq>(
+    reference: &[T],
+    hypothesis: &[T],
+    offset: (usize, usize),
+    path: &mut Vec<(Edit, usize, usize)>,
+) {
+    let n = reference.len();
+    let m = hypothesis.len();
+    if n <= 1 || m <= 1 || (n + 1).saturating_mul(m + 1) <= TABLE_CELLS {
+        path.extend(
+            align_table(reference, hypothesis)
+                .into_iter()
+                .map(|(edit, i, j)| (edit, i + offset.0, j + offset.1)),
+        );
+        return;
+    }
+    let mid = n / 2;
+    let upper = last_row(&reference[..mid], hypothesis);
+    let lower_reference: Vec<&T> = reference[mid..].iter().rev().collect();
+    let lower_hypothesis: Vec<&T> = hypothesis.iter().rev().collect();
+    let lower = last_row(&lower_reference, &lower_hypothesis);
+    let split = (0..=m)
+        .min_by_key(|&j| upper[j] + lower[m - j])
+        .unwrap_or(0);
+    align_into(&reference[..mid], &hypothesis[..split], offset, path);
+    align_into(
+        &reference[mid..],
+        &hypothesis[split..],
+        (offset.0 + mid, offset.1 + split),
+        path,
+    );
+}
+
+fn align<T: PartialEq>(reference: &[T], hypothesis: &[T]) -> Vec<(Edit, usize, usize)> {
+    let mut path = Vec::with_capacity(reference.len() + hypothesis.len());
+    align_into(reference, hypothesis, (0, 0), &mut path);
+    path
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Script {
+    Kanji,
+    Hiragana,
+    Katakana,
+    Alphanumeric,
+    Other,
+}
+
+fn script_of(c: char) -> Script {
+    match c {
+        '\u{3041}'..='\u{309f}' => Script::Hiragana,
+        '\u{30a1}'..='\u{30fa}' | '\u{30fd}'..='\u{30ff}' | '\u{31f0}'..='\u{31ff}' => {
+            Script::Katakana
+        }
+        '\u{ff66}'..='\u{ff9f}' => Script::Katakana,
+        '\u{3400}'..='\u{4dbf}' | '\u{4e00}'..='\u{9fff}' | '\u{f900}'..='\u{faff}' => {
+            Script::Kanji
+        }
+        '々' | '〆' => Script::Kanji,
+        c if c.is_alphanumeric() => Script::Alphanumeric,
+        _ => Script::Other,
+    }
+}
+
+// japanese has no spaces between words, so texts without recognizer tokens are split at whitespace
+// and between runs of kanji, hiragana, katakana and alphanumerics. other characters are words by themselves.
+pub fn segment_words(text: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    for chunk in text.split_whitespace() {
+        let mut current = String::new();
+        let mut script = Script::Other;
+        for c in chunk.chars() {
+            // the prolonged sound mark belongs to the preceding run
+            if c == 'ー' && !current.is_empty() {
+                current.push(c);
+                continue;
+            }
+            let next = script_of(c);
+            if !current.is_empty() && (next != script || next == Script::Other) {
+                words.push(std::mem::take(&mut current));
+            }
+            current.push(c);
+            script = next;
+        }
+        if !current.is_empty() {
+            words.push(current);
+        }
+    }
+    words
+}
+
+// edit with the aligned reference and hypothesis unit indices
+type AlignedEdit = (Edit, Option<usize>, Option<usize>);
+
+// reference and hypothesis are aligned as a whole so that differently segmented utterances can be compared.
+// each edit is attributed to the reference utterance of the aligned reference unit
+// (insertions go to the utterance of the preceding reference unit).
+fn score_by_utterance<T: PartialEq>(
+    reference: &[(usize, T)],
+    hypothesis: &[T],
+    count: usize,
+) -> (Score, Vec<Score>, Vec<Vec<AlignedEdit>>) {
+    let refs: Vec<&T> = reference.iter().map(|(_, unit)| unit).collect();
+    let hyps: Vec<&T> = hypothesis.iter().collect();
+    let mut total = Score::default();
+    let mut scores = vec![Score::default(); count];
+    let mut edits = vec![Vec::new(); count];
+    let mut current = 0;
+    for (edit, i, j) in align(&refs, &hyps) {
+        if edit != Edit::Insert {
+            current = reference[i].0;
+        }
+        total.add(edit);
+        if count > 0 {
+            scores[current].add(edit);
+            edits[current].push((
+                edit,
+                if edit == Edit::Insert { None } else { Some(i) },
+                if edit == Edit::Delete { None } else { Some(j) },
+            ));
+        }
+    }
+    (total, scores, edits)
+}
+
+fn diff_text(reference: &[char], hypothesis: &[char], edits: &[AlignedEdit]) -> String {
+    let mut s = String::new();
+    let mut deleted = String::new();
+    let mut inserted = String::new();
+    let flush = |s: &mut String, deleted: &mut String, inserted: &mut String| {
+        if !deleted.is_empty() {
+            s.push_str(&format!("[-{}-]", deleted));
+            deleted.clear();
+        }
+        if !inserted.is_empty() {
+            s.push_str(&format!("{{+{}+}}", inserted));
+            inserted.clear();
+        }
+    };
+    for (edit, i, j) in edits {
+        match edit {
+            Edit::Equal => {
+                flush(&mut s, &mut deleted, &mut inserted);
+                s.push(reference[i.unwrap()]);
+            }
+            Edit::Substitute => {
+                deleted.push(reference[i.unwrap()]);
+                inserted.push(hypothesis[j.unwrap()]);
+            }
+            Edit::Delete => deleted.push(reference[i.unwrap()]),
+            Edit::Insert => inserted.push(hypothesis[j.unwrap()]),
+        }
+    }
+    flush(&mut s, &mut deleted, &mut inserted);
+    s
+}
+
+// utterances are expected to be normalized already. CER ignores whitespace.
+// WER counts recognizer tokens when both sides have them, otherwise both texts are split by segment_words
+// so that the reference and the hypothesis are always segmented the same way.
+pub fn evaluate(reference: &[Utterance], hypothesis: &[Utterance]) -> Report {
+    let has_tokens = |utterances: &[Utterance]| {
+        utterances
+            .iter()
+            .all(|utterance| utterance.text.trim().is_empty() || !utterance.tokens.is_empty())
+    };
+    let word_unit = if has_tokens(reference) && has_tokens(hypothesis) {
+        WordUnit::Token
+    } else {
+        WordUnit::CharacterType
+    };
+    let words_of = |utterance: &Utterance| -> Vec<String> {
+        match word_unit {
+            WordUnit::Token => utterance
+                .tokens
+                .iter()
+                .map(|token| token.written.split_whitespace().collect::<String>())
+                .filter(|word| !word.is_empty())
+                .collect(),
+            WordUnit::CharacterType => segment_words(&utterance.text),
+        }
+    };
+
+    let ref_chars: Vec<(usize, char)> = reference
+        .iter()
+        .enumerate()
+        .flat_map(|(index, utterance)| {
+            utterance
+                .text
+                .chars()
+                .filter(|c| !c.is_whitespace())
+                .map(move |c| (index, c))
+        })
+        .collect();
+    let hyp_chars: Vec<char> = hypothesis
+        .iter()
+        .flat_map(|utterance| utterance.text.chars().filter(|c| !c.is_whitespace()))
+        .collect();
+    let ref_words: Vec<(usize, String)> = reference
+        .iter()
+        .enumerate()
+        .flat_map(|(index, utterance)| {
+            words_of(utterance)
+                .into_iter()
+                .map(move |word| (index, word))
+        })
+        .collect();
+    let hyp_words: Vec<String> = hypothesis.iter().flat_map(words_of).collect();
+
+    let (cer, cer_scores, char_edits) = score_by_utterance(&ref_chars, &hyp_chars, reference.len());
+    let (wer, wer_scores, _) = score_by_utterance(&ref_words, &hyp_words, reference.len());
+
+    let ref_plain: Vec<char> = ref_chars.iter().map(|(_, c)| *c).collect();
+    let utterances = reference
+        .iter()
+        .enumerate()
+        .map(|(index, utterance)| {
+            let edits = &char_edits[index];
+            UtteranceReport {
+                index,
+                reference: utterance.text.clone(),
+                hypothesis: edits
+                    .iter()
+                    .filter_map(|(_, _, j)| j.map(|j| hyp_chars[j]))
+                    .collect(),
+                cer: cer_scores[index],
+                wer: wer_scores[index],
+                diff: diff_text(&ref_plain, &hyp_chars, edits),
+            }
+        })
+        .collect();
+
+    Report {
+        cer,
+        wer,
+        word_unit,
+        utterances,
+    }
+}
+
+impl Report {
+    pub fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string_pretty(self)
+            .map_err(|e| format!("failed to serialize evaluation report: {}", e))
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut s = format!(
+            "CER {}\nWER {} by {}\n",
+            self.cer.summary(),
+            self.wer.summary(),
+            self.word_unit.description()
+        );
+        for utterance in &self.utterances {
+            s.push_str(&format!(
+                "\n#{} CER {} WER {}\nREF:  {}\nHYP:  {}\nDIFF: {}\n",
+                utterance.index + 1,
+                utterance.cer.summary(),
+                utterance.wer.summary(),
+                utterance.reference,
+                utterance.hypothesis,
+                utterance.diff
+            ));
+        }
+        s
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transcript::Token;
+
+    fn cost(path: &[(Edit, usize, usize)]) -> usize {
+        path.iter()
+            .filter(|(edit, _, _)| *edit != Edit::Equal)
+            .count()
+    }
+
+    fn text(s: &str) -> Utterance {
+        Utterance {
+            text: s.to_string(),
+            confidence: 1.0,
+            starttime: 0,
+            endtime: 0,
+            speaker: None,
+            position: None,
+            tokens: Vec::new(),
+        }
+    }
+
+    fn tokens(words: &[&str]) -> Utterance {
+        Utterance::from_tokens(
+            words
+                .iter()
+                .map(|word| Token {
+                    written: word.to_string(),
+                    spoken: String::new(),
+                    confidence: 1.0,
+                    starttime: 0,
+                    endtime: 0,
+                    speaker: None,
+                })
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn align_counts_edits() {
+        let reference: Vec<char> = "kitten".chars().collect();
+        let hypothesis: Vec<char> = "sitting".chars().collect();
+        let path = align(&reference, &hypothesis);
+        assert_eq!(cost(&path), 3);
+        assert_eq!(
+            path.iter().filter(|(e, _, _)| *e != Edit::Insert).count(),
+            6
+        );
+        assert_eq!(
+            path.iter().filter(|(e, _, _)| *e != Edit::Delete).count(),
+            7
+        );
+    }
+
+    #[test]
+    fn split_alignment_matches_table() {
+        // long enough to go through the Hirschberg split
+        let reference: Vec<u32> = (0..700).map(|i| i * 7 % 13).collect();
+        let hypothesis: Vec<u32> = (0..650).map(|i| i * 5 % 11).collect();
+        let split = align(&reference, &hypothesis);
+        let table = align_table(&reference, &hypothesis);
+        assert_eq!(cost(&split), cost(&table));
+
+        // indices walk through both sequences in order
+        let (mut i, mut j) = (0, 0);
+        for (edit, ri, hj) in split {
+            match edit {
+                Edit::Equal | Edit::Substitute => {
+                    assert_eq!((ri, hj), (i, j));
+                    i += 1;
+                    j += 1;
+                }
+                Edit::Delete => {
+                    assert_eq!(ri, i);
+                    i += 1;
+                }
+                Edit::Insert => {
+                    assert_eq!(hj, j);
+                    j += 1;
+                }
+            }
+        }
+        assert_eq!((i, j), (reference.len(), hypothesis.len()));
+    }
+
+    #[test]
+    fn segment_words_splits_by_character_type() {
+        assert_eq!(
+            segment_words("今日はAIの話をしますスーパー 2回"),
+            vec![
+                "今日",
+                "は",
+                "AI",
+                "の",
+                "話",
+                "をします",
+                "スーパー",
+                "2",
+                "回"
+            ]
+        );
+    }
+
+    #[test]
+    fn evaluate_attributes_edits_across_segmentation() {
+        let reference = vec![text("今日は晴れ"), text("明日は雨")];
+        let hypothesis = vec![text("今日は晴れ明日は"), text("飴")];
+        let report = evaluate(&reference, &hypothesis);
+        assert_eq!(report.word_unit, WordUnit::CharacterType);
+        assert_eq!(report.cer.reference_length, 9);
+        assert_eq!(report.cer.errors(), 1);
+        assert_eq!(report.utterances[0].cer.errors(), 0);
+        assert_eq!(report.utterances[1].cer.substitutions, 1);
+        assert_eq!(report.utterances[1].diff, "明日は[-雨-]{+飴+}");
+    }
+
+    #[test]
+    fn evaluate_counts_recognizer_tokens() {
+        let reference = vec![tokens(&["今日", "は", "晴れ"])];
+        let hypothesis = vec![tokens(&["今日", "は", "腫れ"])];
+        let report = evaluate(&reference, &hypothesis);
+        assert_eq!(report.word_unit, WordUnit::Token);
+        assert_eq!(report.wer.reference_length, 3);
+        assert_eq!(report.wer.substitutions, 1);
+
+        // a text-only side makes both sides fall back to the same segmentation
+        let report = evaluate(&[text("今日は晴れ")], &hypothesis);
+        assert_eq!(report.word_unit, WordUnit::CharacterType);
+        assert_eq!(report.wer.reference_length, 4);
+        assert_eq!(report.wer.substitutions, 1);
+    }
+}
@@ -1,12 +1,20 @@
+use crate::engines::Engine;
+use crate::settings::json_object_to_values;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Default, Serialize, Deserialize)]
 struct Config {
-    api_key: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    api_key: Option<String>,
+    #[serde(default, skip_serializing_if = "serde_json::Map::is_empty")]
+    transcribe: serde_json::Map<String, serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    engines: Vec<Engine>,
 }
 
-fn resolve_config_path() -> Result<PathBuf, String> {
+pub fn resolve_config_path() -> Result<PathBuf, String> {
     if let Some(home) = dirs::home_dir() {
         Ok(home.join(format!(".config/{}/config.json", crate_name!())))
     } else {
@@ -14,19 +22,28 @@ fn resolve_config_path() -> Result<PathBuf, String> {
     }
 }
 
-fn load_config() -> Result<Config, String> {
+fn load_config() -> Result<Option<Config>, String> {
     let path = resolve_config_path()?;
+    if !path.exists() {
+        return Ok(None);
+    }
     let text =
         std::fs::read_to_string(&path).map_err(|e| format!("failed to read config: {}", e))?;
-    serde_json::from_str(&text).map_err(|e| format!("failed to deserialize config: {}", e))
+    serde_json::from_str(&text)
+        .map(Some)
+        .map_err(|e| format!("failed to deserialize config: {}", e))
 }
 
 pub fn load_api_key() -> Result<Option<String>, String> {
-    let path = resolve_config_path()?;
-    if !path.exists() {
-        return Ok(None);
-    }
-    load_config().map(|c| Some(c.api_key.clone()))
+    load_config().map(|c| c.and_then(|c| c.api_key))
+}
+
+pub fn load_global_settings() -> Result<Option<BTreeMap<String, Vec<String>>>, String> {
+    load_config().map(|c| c.map(|c| json_object_to_values(&c.transcribe)))
+}
+
+pub fn load_custom_engines() -> Result<Vec<Engine>, String> {
+    load_config().map(|c| c.map(|c| c.engines).unwrap_or_default())
 }
 
 fn save_config(c: &Config) -> Result<(), String> {
@@ -46,9 +63,8 @@ fn save_config(c: &Config) -> Result<(), String> {
     std::fs::write(path_file, text).map_err(|e| format!("failed to write config: {}", e))
 }
 
-pub fn save_api_key(api_key: &String) -> Result<(), String> {
-    let c = Config {
-        api_key: api_key.clone(),
-    };
+pub fn save_api_key(api_key: &str) -> Result<(), String> {
+    let mut c = load_config()?.unwrap_or_default();
+    c.api_key = Some(api_key.to_string());
     save_config(&c)
 }
@@ -0,0 +1,75 @@
+use native_tls::TlsConnector;
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use url::{form_urlencoded, Url};
+
+pub fn post_form(url: &Url, params: &[(&str, &str)]) -> Result<String, String> {
+    let host = url.host_str().ok_or("failed to resolve host of url")?;
+    let port = url
+        .port_or_known_default()
+        .ok_or("failed to resolve port of url")?;
+    let body = form_urlencoded::Serializer::new(String::new())
+        .extend_pairs(params)
+        .finish();
+    let request = format!(
+        "POST {} HTTP/1.0\r\nHost: {}\r\nUser-Agent: {}/{}\r\nContent-Type: application/x-www-form-urlencoded\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        url.path(),
+        host,
+        crate_name!(),
+        crate_version!(),
+        body.len(),
+        body
+    );
+
+    let stream =
+        TcpStream::connect((host, port)).map_err(|e| format!("failed to connect: {}", e))?;
+    let mut response = Vec::new();
+    if url.scheme() == "https" {
+        let connector =
+            TlsConnector::new().map_err(|e| format!("failed to create tls connector: {}", e))?;
+        let mut stream = connector
+            .connect(host, stream)
+            .map_err(|e| format!("failed to establish tls connection: {}", e))?;
+        send_request(&mut stream, &request, &mut response)?;
+    } else {
+        let mut stream = stream;
+        send_request(&mut stream, &request, &mut response)?;
+    }
+
+    parse_response(&response)
+}
+
+fn send_request<S: Read + Write>(
+    stream: &mut S,
+    request: &str,
+    response: &mut Vec<u8>,
+) -> Result<(), String> {
+    stream
+        .write_all(request.as_bytes())
+        .map_err(|e| format!("failed to send request: {}", e))?;
+    stream
+        .read_to_end(response)
+        .map_err(|e| format!("failed to read response: {}", e))?;
+    Ok(())
+}
+
+fn parse_response(response: &[u8]) -> Result<String, String> {
+    let text = String::from_utf8_lossy(response);
+    let (head, body) = match text.find("\r\n\r\n") {
+        Some(index) => (&text[..index], &text[index + 4..]),
+        None => return Err("failed to parse response: header is not terminated".to_string()),
+    };
+    let status_line = head.lines().next().unwrap_or("");
+    let status: u16 = status_line
+        .split_whitespace()
+        .nth(1)
+        .ok_or("failed to parse response: status code is missing")?
+        .parse()
+        .map_err(|e| format!("failed to parse response status code: {}", e))?;
+
+    if (200..300).contains(&status) {
+        Ok(body.to_string())
+    } else {
+        Err(format!("request failed ({}): {}", status_line, body.trim()))
+    }
+}
@@ -0,0 +1,283 @@
+use crate::ami::JsonOutput;
+use crate::transcript::{join_tokens, Token, Utterance, INCOMPLETE_NOTICE};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+// reads utterances back from any format written by transcribe or render.
+// formats are detected from the content, anything unrecognized is read as text (one utterance per line).
+// json and words json keep the recognizer tokens, the other formats only have the text (tokens are empty).
+pub fn load_utterances(path: &str) -> Result<Vec<Utterance>, String> {
+    let text =
+        std::fs::read_to_string(path).map_err(|e| format!("failed to read {}: {}", path, e))?;
+    let head = text.trim_start_matches('\u{feff}').trim_start();
+
+    if head.starts_with('{') {
+        return Ok(JsonOutput::load(path)?
+            .transcript()
+            .utterances
+            .into_iter()
+            .filter(|utterance| !utterance.text.trim().is_empty())
+            .collect());
+    } else if head.starts_with('[') && !head.starts_with("[Script Info]") {
+        return from_words_json(path, head);
+    }
+
+    let texts = if head.starts_with("[Script Info]") {
+        from_ass(head)
+    } else if head.starts_with("WEBVTT") || is_srt(head) {
+        from_cues(head)
+    } else if head.starts_with("File type = \"ooTextFile\"") {
+        from_textgrid(head)
+    } else if head.contains("<ANNOTATION_DOCUMENT") {
+        from_eaf(head)
+    } else if head.starts_with('<') {
+        from_paragraphs(head)
+    } else if is_audacity(head) {
+        head.lines()
+            .filter_map(|line| line.splitn(3, '\t').nth(2))
+            .map(|s| s.to_string())
+            .collect()
+    } else {
+        head.lines()
+            .map(|line| line.replace("[?", "").replace("?]", ""))
+            .collect()
+    };
+
+    Ok(texts
+        .into_iter()
+        .map(|s: String| s.trim().to_string())
+        .filter(|s| !s.is_empty() && s != INCOMPLETE_NOTICE)
+        .map(|text| Utterance {
+            text,
+            confidence: 1.0,
+            starttime: 0,
+            endtime: 0,
+            speaker: None,
+            position: None,
+            tokens: Vec::new(),
+        })
+        .collect())
+}
+
+fn from_words_json(path: &str, text: &str) -> Result<Vec<Utterance>, String> {
+    #[derive(Deserialize)]
+    struct Word {
+        utterance: usize,
+        #[serde(flatten)]
+        token: Token,
+    }
+    let words: Vec<Word> = serde_json::from_str(text)
+        .map_err(|e| format!("failed to parse {} as words json: {}", path, e))?;
+    let mut utterances: BTreeMap<usize, Vec<Token>> = BTreeMap::new();
+    for word in words {
+        utterances
+            .entry(word.utterance)
+            .or_default()
+            .push(word.token);
+    }
+    Ok(utterances
+        .into_values()
+        .map(Utterance::from_tokens)
+        .filter(|utterance| !utterance.text.trim().is_empty())
+        .collect())
+}
+
+fn join_lines<'a>(lines: impl Iterator<Item = &'a str>) -> String {
+    join_tokens(
+        lines
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty()),
+    )
+}
+
+fn is_srt(text: &str) -> bool {
+    let mut lines = text.lines();
+    matches!(lines.next(), Some(index) if index.trim().parse::<u64>().is_ok())
+        && matches!(lines.next(), Some(timing) if timing.contains("-->"))
+}
+
+fn from_cues(text: &str) -> Vec<String> {
+    let mut texts = Vec::new();
+    let mut lines = text.lines();
+    while let Some(line) = lines.next() {
+        if !line.contains("-->") {
+            continue;
+        }
+        let cue: Vec<String> = lines
+            .by_ref()
+            .take_while(|line| !line.trim().is_empty())
+            .map(strip_tags)
+            .collect();
+        texts.push(join_lines(cue.iter().map(|s| s.as_str())));
+    }
+    texts
+}
+
+fn from_ass(text: &str) -> Vec<String> {
+    text.lines()
+        .filter_map(|line| line.strip_prefix("Dialogue:"))
+        .filter_map(|line| line.splitn(10, ',').nth(9))
+        .map(|s| join_lines(s.split("\\N")))
+        .collect()
+}
+
+// annotation outputs hold utterances in an "utterance" tier, or one "utterance (speaker)" tier per speaker
+fn is_utterance_tier(name: &str) -> bool {
+    name == "utterance" || name.starts_with("utterance (")
+}
+
+fn by_starttime(mut utterances: Vec<(f64, String)>) -> Vec<String> {
+    utterances.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+    utterances.into_iter().map(|(_, text)| text).collect()
+}
+
+fn from_textgrid(text: &str) -> Vec<String> {
+    let unquote = |s: &str| s.trim_end_matches('"').replace("\"\"", "\"");
+    let mut utterances = Vec::new();
+    // the header is followed by "item []:" and then one "item [n]:" per tier
+    for tier in text.split("item [").skip(2) {
+        let name = tier
+            .lines()
+            .find_map(|line| line.trim().strip_prefix("name = \""))
+            .map(unquote)
+            .unwrap_or_default();
+        if !is_utterance_tier(&name) {
+            continue;
+        }
+        let mut xmin = 0.0;
+        for line in tier.split("intervals [").skip(1).flat_map(|s| s.lines()) {
+            let line = line.trim();
+            if let Some(value) = line.strip_prefix("xmin = ") {
+                xmin = value.parse().unwrap_or(0.0);
+            } else if let Some(value) = line.strip_prefix("text = \"") {
+                utterances.push((xmin, unquote(value)));
+            }
+        }
+    }
+    by_starttime(utterances)
+}
+
+fn attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    let pattern = format!(" {}=\"", name);
+    let start = tag.find(&pattern)? + pattern.len();
+    tag[start..].split('"').next()
+}
+
+fn from_eaf(text: &str) -> Vec<String> {
+    let slots: BTreeMap<&str, f64> = text
+        .split("<TIME_SLOT ")
+        .skip(1)
+        .filter_map(|tag| {
+            let value = attribute(tag, "TIME_VALUE")?.parse().ok()?;
+            Some((attribute(tag, "TIME_SLOT_ID")?, value))
+        })
+        .collect();
+    let mut utterances = Vec::new();
+    for tier in text.split("<TIER ").skip(1) {
+        let tier = &tier[..tier.find("</TIER>").unwrap_or(tier.len())];
+        if !attribute(tier, "TIER_ID").is_some_and(|name| is_utterance_tier(&unescape_xml(name))) {
+            continue;
+        }
+        for annotation in tier.split("<ALIGNABLE_ANNOTATION").skip(1) {
+            let starttime = attribute(annotation, "TIME_SLOT_REF1")
+                .and_then(|id| slots.get(id))
+                .copied()
+                .unwrap_or(0.0);
+            for value in elements(annotation, "ANNOTATION_VALUE") {
+                utterances.push((starttime, unescape_xml(value)));
+            }
+        }
+    }
+    by_starttime(utterances)
+}
+
+// html (ruby) and ttml keep one utterance or cue per <p> element
+fn from_paragraphs(text: &str) -> Vec<String> {
+    elements(text, "p")
+        .into_iter()
+        .map(|inner| {
+            let inner = remove_elements(inner, "rt");
+            let lines: Vec<String> = inner
+                .split("<br/>")
+                .map(|line| unescape_xml(&strip_tags(line)))
+                .collect();
+            join_lines(lines.iter().map(|s| s.as_str()))
+        })
+        .collect()
+}
+
+fn is_audacity(text: &str) -> bool {
+    text.lines().filter(|line| !line.trim().is_empty()).all(|line| {
+        let mut columns = line.splitn(3, '\t');
+        matches!(
+            (columns.next(), columns.next(), columns.next()),
+            (Some(start), Some(end), Some(_)) if start.parse::<f64>().is_ok() && end.parse::<f64>().is_ok()
+        )
+    })
+}
+
+fn elements<'a>(text: &'a str, name: &str) -> Vec<&'a str> {
+    let open = format!("<{}", name);
+    let close = format!("</{}>", name);
+    let mut found = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find(&open) {
+        let after = &rest[start + open.len()..];
+        // skip elements whose name only starts with `name` (e.g. <pre> for <p>)
+        if !after.starts_with('>') && !after.starts_with(' ') {
+            rest = after;
+            continue;
+        }
+        let content_start = match after.find('>') {
+            Some(i) => i + 1,
+            None => break,
+        };
+        let content = &after[content_start..];
+        match content.find(&close) {
+            Some(end) => {
+                found.push(&content[..end]);
+                rest = &content[end + close.len()..];
+            }
+            None => break,
+        }
+    }
+    found
+}
+
+fn remove_elements(text: &str, name: &str) -> String {
+    let open = format!("<{}>", name);
+    let close = format!("</{}>", name);
+    let mut s = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find(&open) {
+        s.push_str(&rest[..start]);
+        rest = match rest[start..].find(&close) {
+            Some(end) => &rest[start + end + close.len()..],
+            None => "",
+        };
+    }
+    s.push_str(rest);
+    s
+}
+
+fn strip_tags(text: &str) -> String {
+    let mut s = String::with_capacity(text.len());
+    let mut in_tag = false;
+    for c in text.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            c if !in_tag => s.push(c),
+            _ => (),
+        }
+    }
+    s
+}
+
+fn unescape_xml(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
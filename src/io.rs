@@ -1,4 +1,6 @@
-use std::io::Write;
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::time::Duration;
 
 pub fn get_input<T>(message: T) -> Result<String, String>
 where
@@ -16,3 +18,183 @@ where
 
     Ok(s.trim().to_string())
 }
+
+pub fn get_secret_input<T>(message: T) -> Result<String, String>
+where
+    T: std::fmt::Display,
+{
+    if !is_stdin_tty() {
+        return get_input(message);
+    }
+
+    let guard = EchoGuard::disable()?;
+    let result = get_input(message);
+    drop(guard);
+    println!();
+
+    result
+}
+
+pub fn read_all_stdin() -> Result<String, String> {
+    let mut s = String::new();
+    std::io::stdin()
+        .read_to_string(&mut s)
+        .map_err(|e| format!("failed to read stdin: {}", e))?;
+
+    Ok(s.trim().to_string())
+}
+
+#[cfg(unix)]
+pub fn is_stdin_tty() -> bool {
+    unsafe { libc::isatty(libc::STDIN_FILENO) == 1 }
+}
+
+// a redirected stdin has no console mode
+#[cfg(windows)]
+pub fn is_stdin_tty() -> bool {
+    console_mode().is_some()
+}
+
+#[cfg(windows)]
+fn console_mode() -> Option<(winapi::um::winnt::HANDLE, u32)> {
+    use winapi::um::{consoleapi, handleapi, processenv, winbase};
+
+    let handle = unsafe { processenv::GetStdHandle(winbase::STD_INPUT_HANDLE) };
+    if handle.is_null() || handle == handleapi::INVALID_HANDLE_VALUE {
+        return None;
+    }
+    let mut mode = 0;
+    if unsafe { consoleapi::GetConsoleMode(handle, &mut mode) } == 0 {
+        return None;
+    }
+    Some((handle, mode))
+}
+
+// other platforms are treated as non-interactive so that they never block on a prompt
+#[cfg(not(any(unix, windows)))]
+pub fn is_stdin_tty() -> bool {
+    false
+}
+
+// waits until the socket is readable (or writable with is_write), the timeout passes or a signal arrives.
+// callers check what is ready by retrying non-blocking reads and writes.
+#[cfg(unix)]
+pub fn wait_socket(
+    socket: &TcpStream,
+    is_write: bool,
+    timeout: Option<Duration>,
+) -> Result<(), String> {
+    use std::os::unix::io::AsRawFd;
+
+    let mut fd = libc::pollfd {
+        fd: socket.as_raw_fd(),
+        events: libc::POLLIN | if is_write { libc::POLLOUT } else { 0 },
+        revents: 0,
+    };
+    // rounded up so that a short wait does not turn into a busy loop
+    let timeout = match timeout {
+        Some(timeout) => timeout.as_micros().div_ceil(1000).min(i32::MAX as u128) as i32,
+        None => -1,
+    };
+    if unsafe { libc::poll(&mut fd, 1, timeout) } < 0 {
+        let e = std::io::Error::last_os_error();
+        if e.kind() != std::io::ErrorKind::Interrupted {
+            return Err(format!("failed to wait for socket: {}", e));
+        }
+    }
+    Ok(())
+}
+
+// without poll the socket is checked at short intervals
+#[cfg(not(unix))]
+pub fn wait_socket(
+    _socket: &TcpStream,
+    _is_write: bool,
+    timeout: Option<Duration>,
+) -> Result<(), String> {
+    let interval = Duration::from_millis(10);
+    std::thread::sleep(timeout.map_or(interval, |timeout| timeout.min(interval)));
+    Ok(())
+}
+
+#[cfg(unix)]
+struct EchoGuard {
+    original: libc::termios,
+}
+
+#[cfg(unix)]
+impl EchoGuard {
+    fn disable() -> Result<EchoGuard, String> {
+        let mut original: libc::termios = unsafe { std::mem::zeroed() };
+        if unsafe { libc::tcgetattr(libc::STDIN_FILENO, &mut original) } != 0 {
+            return Err(format!(
+                "failed to get terminal attributes: {}",
+                std::io::Error::last_os_error()
+            ));
+        }
+
+        let mut hidden = original;
+        hidden.c_lflag &= !libc::ECHO;
+        if unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &hidden) } != 0 {
+            return Err(format!(
+                "failed to disable terminal echo: {}",
+                std::io::Error::last_os_error()
+            ));
+        }
+
+        Ok(EchoGuard { original })
+    }
+}
+
+#[cfg(unix)]
+impl Drop for EchoGuard {
+    fn drop(&mut self) {
+        unsafe {
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &self.original);
+        }
+    }
+}
+
+#[cfg(windows)]
+struct EchoGuard {
+    handle: winapi::um::winnt::HANDLE,
+    original: u32,
+}
+
+#[cfg(windows)]
+impl EchoGuard {
+    fn disable() -> Result<EchoGuard, String> {
+        use winapi::um::{consoleapi, wincon};
+
+        let (handle, original) =
+            console_mode().ok_or("failed to get console mode: stdin is not a console")?;
+        if unsafe { consoleapi::SetConsoleMode(handle, original & !wincon::ENABLE_ECHO_INPUT) } == 0
+        {
+            return Err(format!(
+                "failed to disable console echo: {}",
+                std::io::Error::last_os_error()
+            ));
+        }
+
+        Ok(EchoGuard { handle, original })
+    }
+}
+
+#[cfg(windows)]
+impl Drop for EchoGuard {
+    fn drop(&mut self) {
+        unsafe {
+            winapi::um::consoleapi::SetConsoleMode(self.handle, self.original);
+        }
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+struct EchoGuard;
+
+#[cfg(not(any(unix, windows)))]
+impl EchoGuard {
+    fn disable() -> Result<EchoGuard, String> {
+        Ok(EchoGuard)
+    }
+}
@@ -0,0 +1,43 @@
+const HIRAGANA_START: u32 = 0x3041;
+const HIRAGANA_END: u32 = 0x3096;
+const KATAKANA_START: u32 = 0x30a1;
+const KATAKANA_END: u32 = 0x30f6;
+const KANA_OFFSET: u32 = KATAKANA_START - HIRAGANA_START;
+
+pub fn to_katakana(s: &str) -> String {
+    s.chars()
+        .map(|c| {
+            let code = c as u32;
+            if (HIRAGANA_START..=HIRAGANA_END).contains(&code) {
+                std::char::from_u32(code + KANA_OFFSET).unwrap_or(c)
+            } else {
+                c
+            }
+        })
+        .collect()
+}
+
+pub fn to_hiragana(s: &str) -> String {
+    s.chars()
+        .map(|c| {
+            let code = c as u32;
+            if (KATAKANA_START..=KATAKANA_END).contains(&code) {
+                std::char::from_u32(code - KANA_OFFSET).unwrap_or(c)
+            } else {
+                c
+            }
+        })
+        .collect()
+}
+
+pub fn is_kana(c: char) -> bool {
+    let code = c as u32;
+    (HIRAGANA_START..=HIRAGANA_END).contains(&code)
+        || (KATAKANA_START..=KATAKANA_END).contains(&code)
+        || c == 'ー'
+}
+
+pub fn is_kanji(c: char) -> bool {
+    let code = c as u32;
+    (0x4e00..=0x9fff).contains(&code) || (0x3400..=0x4dbf).contains(&code) || c == '々'
+}
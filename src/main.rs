@@ -2,13 +2,37 @@
 extern crate clap;
 
 mod ami;
+mod audio;
+mod cache;
+mod cleanup;
 mod cmd;
 mod cmd_base;
+mod compare;
+mod engines;
+mod eval;
 mod fs;
+mod http;
+mod import;
 mod io;
+mod kana;
+mod output;
+mod pacing;
+mod session;
+mod settings;
+mod shaping;
+mod signal;
+mod timeout;
+mod transcript;
 
 use clap::App;
+use cmd::cache::Cache;
+use cmd::compare::Compare;
+use cmd::config::Config;
 use cmd::configure::Configure;
+use cmd::engines::Engines;
+use cmd::eval::Eval;
+use cmd::issue_token::IssueToken;
+use cmd::render::Render;
 use cmd::transcribe::Transcribe;
 use cmd_base::CmdBase;
 
@@ -19,15 +43,29 @@ fn main() {
         .version(crate_version!())
         .subcommand(Configure::subcommand())
         .subcommand(Transcribe::subcommand())
+        .subcommand(IssueToken::subcommand())
+        .subcommand(Config::subcommand())
+        .subcommand(Render::subcommand())
+        .subcommand(Cache::subcommand())
+        .subcommand(Eval::subcommand())
+        .subcommand(Compare::subcommand())
+        .subcommand(Engines::subcommand())
         .get_matches();
 
     let result = match maches.subcommand() {
-        (Configure::NAME, Some(args)) => Configure::run(&args),
-        (Transcribe::NAME, Some(args)) => Transcribe::run(&args),
+        (Configure::NAME, Some(args)) => Configure::run(args),
+        (Transcribe::NAME, Some(args)) => Transcribe::run(args),
+        (IssueToken::NAME, Some(args)) => IssueToken::run(args),
+        (Config::NAME, Some(args)) => Config::run(args),
+        (Render::NAME, Some(args)) => Render::run(args),
+        (Cache::NAME, Some(args)) => Cache::run(args),
+        (Eval::NAME, Some(args)) => Eval::run(args),
+        (Compare::NAME, Some(args)) => Compare::run(args),
+        (Engines::NAME, Some(args)) => Engines::run(args),
         _ => Err("No subcommand chosen. Add --help | -h to view the subcommands.".to_string()),
     };
     if let Err(msg) = result {
         eprint!("{}", msg);
-        std::process::exit(1);
+        std::process::exit(signal::exit_code().unwrap_or(1));
     }
 }
@@ -0,0 +1,232 @@
+use crate::output::{escape_xml, RenderOptions};
+use crate::transcript::Transcript;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+struct Interval {
+    starttime: u64,
+    endtime: u64,
+    text: String,
+}
+
+struct Tier {
+    name: String,
+    participant: Option<String>,
+    intervals: Vec<Interval>,
+}
+
+struct SpeakerTiers<'a> {
+    speaker: Option<&'a str>,
+    utterances: Vec<Interval>,
+    words: Vec<Interval>,
+}
+
+fn tiers_of<'a, 'b>(
+    groups: &'b mut Vec<SpeakerTiers<'a>>,
+    speaker: Option<&'a str>,
+) -> &'b mut SpeakerTiers<'a> {
+    let index = match groups.iter().position(|group| group.speaker == speaker) {
+        Some(index) => index,
+        None => {
+            groups.push(SpeakerTiers {
+                speaker,
+                utterances: Vec::new(),
+                words: Vec::new(),
+            });
+            groups.len() - 1
+        }
+    };
+    &mut groups[index]
+}
+
+fn tier_name(kind: &str, speaker: Option<&str>) -> String {
+    match speaker {
+        Some(speaker) => format!("{} ({})", kind, speaker),
+        None => kind.to_string(),
+    }
+}
+
+// one utterance tier and one word tier per speaker, in order of first appearance.
+// items without a speaker go to the plain "utterance" and "word" tiers.
+fn build_tiers(transcript: &Transcript, options: &RenderOptions) -> Vec<Tier> {
+    let mut groups = Vec::new();
+    for utterance in &transcript.utterances {
+        tiers_of(&mut groups, utterance.speaker.as_deref())
+            .utterances
+            .push(Interval {
+                starttime: utterance.starttime,
+                endtime: utterance.endtime,
+                text: options.utterance_text(utterance),
+            });
+        for token in &utterance.tokens {
+            let speaker = token.speaker.as_deref().or(utterance.speaker.as_deref());
+            tiers_of(&mut groups, speaker).words.push(Interval {
+                starttime: token.starttime,
+                endtime: token.endtime,
+                text: options.token_text(token),
+            });
+        }
+    }
+    if groups.is_empty() {
+        tiers_of(&mut groups, None);
+    }
+
+    groups
+        .into_iter()
+        .flat_map(|group| {
+            let participant = group.speaker.map(|s| s.to_string());
+            vec![
+                Tier {
+                    name: tier_name("utterance", group.speaker),
+                    participant: participant.clone(),
+                    intervals: group.utterances,
+                },
+                Tier {
+                    name: tier_name("word", group.speaker),
+                    participant,
+                    intervals: group.words,
+                },
+            ]
+        })
+        .collect()
+}
+
+fn format_seconds(millis: u64) -> String {
+    let s = format!("{}.{:03}", millis / 1000, millis % 1000);
+    s.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+fn escape_textgrid(s: &str) -> String {
+    s.replace('"', "\"\"")
+}
+
+// Praat requires interval tiers to cover the whole time domain without gaps or overlaps.
+fn fill_gaps(intervals: &[Interval], xmax: u64) -> Vec<(u64, u64, &str)> {
+    let mut filled = Vec::new();
+    let mut cursor = 0;
+    for interval in intervals {
+        let starttime = interval.starttime.max(cursor);
+        let endtime = interval.endtime.min(xmax);
+        if endtime <= starttime {
+            continue;
+        }
+        if starttime > cursor {
+            filled.push((cursor, starttime, ""));
+        }
+        filled.push((starttime, endtime, interval.text.as_str()));
+        cursor = endtime;
+    }
+    if cursor < xmax {
+        filled.push((cursor, xmax, ""));
+    }
+    filled
+}
+
+pub fn to_textgrid(transcript: &Transcript, options: &RenderOptions) -> String {
+    let tiers = build_tiers(transcript, options);
+    let xmax = transcript
+        .utterances
+        .iter()
+        .map(|utterance| utterance.endtime)
+        .max()
+        .unwrap_or(0)
+        .max(1);
+
+    let mut s = format!(
+        "File type = \"ooTextFile\"\nObject class = \"TextGrid\"\n\nxmin = 0\nxmax = {}\ntiers? <exists>\nsize = {}\nitem []:\n",
+        format_seconds(xmax),
+        tiers.len()
+    );
+    for (index, tier) in tiers.iter().enumerate() {
+        let intervals = fill_gaps(&tier.intervals, xmax);
+        s.push_str(&format!(
+            "    item [{}]:\n        class = \"IntervalTier\"\n        name = \"{}\"\n        xmin = 0\n        xmax = {}\n        intervals: size = {}\n",
+            index + 1,
+            escape_textgrid(&tier.name),
+            format_seconds(xmax),
+            intervals.len()
+        ));
+        for (i, (starttime, endtime, text)) in intervals.iter().enumerate() {
+            s.push_str(&format!(
+                "        intervals [{}]:\n            xmin = {}\n            xmax = {}\n            text = \"{}\"\n",
+                i + 1,
+                format_seconds(*starttime),
+                format_seconds(*endtime),
+                escape_textgrid(text)
+            ));
+        }
+    }
+    s
+}
+
+fn format_datetime(secs: u64) -> String {
+    // civil date from days since 1970-01-01 (Howard Hinnant's algorithm)
+    let days = (secs / 86400) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let doe = days.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        secs / 3600 % 24,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
+
+pub fn to_eaf(transcript: &Transcript, options: &RenderOptions) -> String {
+    let tiers = build_tiers(transcript, options);
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+
+    let mut slots = Vec::new();
+    let mut annotations = String::new();
+    for tier in &tiers {
+        annotations.push_str(&format!(
+            "  <TIER LINGUISTIC_TYPE_REF=\"default-lt\"{} TIER_ID=\"{}\">\n",
+            tier.participant
+                .as_ref()
+                .map(|p| format!(" PARTICIPANT=\"{}\"", escape_xml(p)))
+                .unwrap_or_default(),
+            escape_xml(&tier.name)
+        ));
+        for interval in &tier.intervals {
+            let id = slots.len() / 2 + 1;
+            slots.push(interval.starttime);
+            slots.push(interval.endtime.max(interval.starttime));
+            annotations.push_str(&format!(
+                "    <ANNOTATION>\n      <ALIGNABLE_ANNOTATION ANNOTATION_ID=\"a{}\" TIME_SLOT_REF1=\"ts{}\" TIME_SLOT_REF2=\"ts{}\">\n        <ANNOTATION_VALUE>{}</ANNOTATION_VALUE>\n      </ALIGNABLE_ANNOTATION>\n    </ANNOTATION>\n",
+                id,
+                slots.len() - 1,
+                slots.len(),
+                escape_xml(&interval.text)
+            ));
+        }
+        annotations.push_str("  </TIER>\n");
+    }
+
+    let mut s = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<ANNOTATION_DOCUMENT AUTHOR=\"{}\" DATE=\"{}\" FORMAT=\"3.0\" VERSION=\"3.0\" xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\" xsi:noNamespaceSchemaLocation=\"http://www.mpi.nl/tools/elan/EAFv3.0.xsd\">\n  <HEADER MEDIA_FILE=\"\" TIME_UNITS=\"milliseconds\"/>\n  <TIME_ORDER>\n",
+        crate_name!(),
+        format_datetime(now)
+    );
+    for (index, time) in slots.iter().enumerate() {
+        s.push_str(&format!(
+            "    <TIME_SLOT TIME_SLOT_ID=\"ts{}\" TIME_VALUE=\"{}\"/>\n",
+            index + 1,
+            time
+        ));
+    }
+    s.push_str("  </TIME_ORDER>\n");
+    s.push_str(&annotations);
+    s.push_str("  <LINGUISTIC_TYPE GRAPHIC_REFERENCES=\"false\" LINGUISTIC_TYPE_ID=\"default-lt\" TIME_ALIGNABLE=\"true\"/>\n</ANNOTATION_DOCUMENT>\n");
+    s
+}
@@ -0,0 +1,92 @@
+use crate::output::layout::{layout, Cue};
+use crate::output::RenderOptions;
+use crate::transcript::Transcript;
+
+const DEFAULT_STYLE: &str = "Default";
+const STYLE_FORMAT: &str = "Format: Name, Fontname, Fontsize, PrimaryColour, SecondaryColour, OutlineColour, BackColour, Bold, Italic, Underline, StrikeOut, ScaleX, ScaleY, Spacing, Angle, BorderStyle, Outline, Shadow, Alignment, MarginL, MarginR, MarginV, Encoding";
+const EVENT_FORMAT: &str =
+    "Format: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text";
+
+// &HAABBGGRR
+const PALETTE: &[&str] = &[
+    "&H00FFFFFF",
+    "&H0000FFFF",
+    "&H00FFFF00",
+    "&H0000FF00",
+    "&H00FF80FF",
+    "&H000080FF",
+];
+
+fn format_time(millis: u64) -> String {
+    let centis = millis / 10;
+    format!(
+        "{}:{:02}:{:02}.{:02}",
+        centis / 360_000,
+        centis / 6000 % 60,
+        centis / 100 % 60,
+        centis % 100
+    )
+}
+
+fn style_name(speaker: &Option<String>) -> String {
+    match speaker {
+        Some(speaker) => speaker.replace(',', "_"),
+        None => DEFAULT_STYLE.to_string(),
+    }
+}
+
+fn style_line(name: &str, colour: &str) -> String {
+    format!(
+        "Style: {},sans-serif,64,{},&H000000FF,&H00000000,&H80000000,0,0,0,0,100,100,0,0,1,3,0,2,60,60,50,1",
+        name, colour
+    )
+}
+
+fn escape_text(cue: &Cue) -> String {
+    cue.lines
+        .iter()
+        .map(|line| {
+            line.replace('{', "｛")
+                .replace('}', "｝")
+                .replace('\\', "＼")
+        })
+        .collect::<Vec<String>>()
+        .join("\\N")
+}
+
+pub fn to_ass(transcript: &Transcript, options: &RenderOptions) -> String {
+    let cues = layout(transcript, options);
+
+    let mut styles = vec![DEFAULT_STYLE.to_string()];
+    for cue in &cues {
+        let name = style_name(&cue.speaker);
+        if !styles.contains(&name) {
+            styles.push(name);
+        }
+    }
+
+    let mut s = String::from(
+        "[Script Info]\nScriptType: v4.00+\nPlayResX: 1920\nPlayResY: 1080\nWrapStyle: 2\nScaledBorderAndShadow: yes\n\n[V4+ Styles]\n",
+    );
+    s.push_str(STYLE_FORMAT);
+    s.push('\n');
+    for (index, name) in styles.iter().enumerate() {
+        s.push_str(&style_line(name, PALETTE[index % PALETTE.len()]));
+        s.push('\n');
+    }
+
+    s.push_str("\n[Events]\n");
+    s.push_str(EVENT_FORMAT);
+    s.push('\n');
+    for cue in &cues {
+        s.push_str(&format!(
+            "Dialogue: 0,{},{},{},{},0,0,0,,{}\n",
+            format_time(cue.starttime),
+            format_time(cue.endtime),
+            style_name(&cue.speaker),
+            cue.speaker.as_deref().unwrap_or("").replace(',', "_"),
+            escape_text(cue)
+        ));
+    }
+    s
+}
@@ -0,0 +1,121 @@
+use crate::output::RenderOptions;
+use crate::transcript::{Transcript, Utterance};
+
+const TITLE_MAX_CHARS: usize = 40;
+const YOUTUBE_MIN_CHAPTER: u64 = 10_000;
+
+struct Chapter {
+    starttime: u64,
+    endtime: u64,
+    title: String,
+}
+
+fn title_of(utterance: &Utterance) -> String {
+    let text = utterance.text.trim();
+    if text.chars().count() > TITLE_MAX_CHARS {
+        let head: String = text.chars().take(TITLE_MAX_CHARS).collect();
+        format!("{}…", head)
+    } else {
+        text.to_string()
+    }
+}
+
+// a chapter starts at every pause of at least `gap` between utterances.
+// the first chapter always starts at 0 and each chapter ends where the next begins.
+fn chapters(transcript: &Transcript, gap: u64) -> Vec<Chapter> {
+    let mut chapters: Vec<Chapter> = Vec::new();
+    let mut last_end = 0;
+    for utterance in &transcript.utterances {
+        if chapters.is_empty() || utterance.starttime.saturating_sub(last_end) >= gap {
+            if let Some(prev) = chapters.last_mut() {
+                prev.endtime = utterance.starttime;
+            }
+            chapters.push(Chapter {
+                starttime: if chapters.is_empty() {
+                    0
+                } else {
+                    utterance.starttime
+                },
+                endtime: utterance.endtime,
+                title: title_of(utterance),
+            });
+        }
+        last_end = last_end.max(utterance.endtime);
+    }
+    if let Some(last) = chapters.last_mut() {
+        last.endtime = last_end.max(last.starttime);
+    }
+    chapters
+}
+
+pub fn to_audacity_labels(transcript: &Transcript, options: &RenderOptions) -> String {
+    transcript
+        .utterances
+        .iter()
+        .map(|utterance| {
+            format!(
+                "{:.6}\t{:.6}\t{}\n",
+                utterance.starttime as f64 / 1000.0,
+                utterance.endtime as f64 / 1000.0,
+                options.utterance_text(utterance).replace(['\t', '\n'], " ")
+            )
+        })
+        .collect()
+}
+
+fn escape_ffmetadata(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        if matches!(c, '=' | ';' | '#' | '\\' | '\n') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+pub fn to_ffmetadata(transcript: &Transcript, options: &RenderOptions) -> String {
+    let mut s = String::from(";FFMETADATA1\n");
+    for chapter in chapters(transcript, options.chapter_gap) {
+        s.push_str(&format!(
+            "\n[CHAPTER]\nTIMEBASE=1/1000\nSTART={}\nEND={}\ntitle={}\n",
+            chapter.starttime,
+            chapter.endtime,
+            escape_ffmetadata(&chapter.title)
+        ));
+    }
+    s
+}
+
+fn format_youtube_time(millis: u64) -> String {
+    let secs = millis / 1000;
+    if secs >= 3600 {
+        format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+    } else {
+        format!("{}:{:02}", secs / 60, secs % 60)
+    }
+}
+
+pub fn to_youtube_chapters(transcript: &Transcript, options: &RenderOptions) -> String {
+    // YouTube ignores chapters shorter than 10 seconds, so such chapters are folded into the previous one
+    let mut kept: Vec<Chapter> = Vec::new();
+    for chapter in chapters(transcript, options.chapter_gap) {
+        match kept.last_mut() {
+            Some(prev)
+                if chapter.starttime.saturating_sub(prev.starttime) < YOUTUBE_MIN_CHAPTER =>
+            {
+                prev.endtime = prev.endtime.max(chapter.endtime);
+            }
+            _ => kept.push(chapter),
+        }
+    }
+    kept.iter()
+        .map(|chapter| {
+            format!(
+                "{} {}\n",
+                format_youtube_time(chapter.starttime),
+                chapter.title.replace('\n', " ")
+            )
+        })
+        .collect()
+}
@@ -0,0 +1,101 @@
+use crate::kana::{is_kana, is_kanji, to_hiragana, to_katakana};
+use crate::output::{escape_xml, RenderOptions};
+use crate::transcript::{Token, Transcript};
+
+fn reading_of(token: &Token) -> Option<&str> {
+    let spoken = token.spoken.trim();
+    if spoken.is_empty() || spoken == "_" {
+        None
+    } else {
+        Some(spoken)
+    }
+}
+
+fn to_reading_lines(transcript: &Transcript, convert: fn(&str) -> String) -> String {
+    transcript
+        .utterances
+        .iter()
+        .map(|utterance| {
+            utterance
+                .tokens
+                .iter()
+                .map(|token| convert(reading_of(token).unwrap_or(&token.written)))
+                .collect::<String>()
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+pub fn to_katakana_reading(transcript: &Transcript) -> String {
+    to_reading_lines(transcript, to_katakana)
+}
+
+pub fn to_hiragana_reading(transcript: &Transcript) -> String {
+    to_reading_lines(transcript, to_hiragana)
+}
+
+fn common_prefix_len(a: &[char], b: &[char]) -> usize {
+    a.iter()
+        .zip(b.iter())
+        .take_while(|(x, y)| is_kana(**x) && to_hiragana(&x.to_string()) == y.to_string())
+        .count()
+}
+
+fn ruby_token(token: &Token) -> String {
+    let reading = match reading_of(token) {
+        Some(reading) if token.written.chars().any(is_kanji) => to_hiragana(reading),
+        _ => return escape_xml(&token.written),
+    };
+
+    let written: Vec<char> = token.written.chars().collect();
+    let reading: Vec<char> = reading.chars().collect();
+    let prefix = common_prefix_len(&written, &reading);
+    let written_rest: Vec<char> = written[prefix..].iter().rev().cloned().collect();
+    let reading_rest: Vec<char> = reading[prefix..].iter().rev().cloned().collect();
+    let suffix = common_prefix_len(&written_rest, &reading_rest);
+    if prefix + suffix >= written.len() || prefix + suffix >= reading.len() {
+        return format!(
+            "<ruby>{}<rt>{}</rt></ruby>",
+            escape_xml(&token.written),
+            escape_xml(&reading.iter().collect::<String>())
+        );
+    }
+
+    let base: String = written[prefix..written.len() - suffix].iter().collect();
+    let rt: String = reading[prefix..reading.len() - suffix].iter().collect();
+    format!(
+        "{}<ruby>{}<rt>{}</rt></ruby>{}",
+        escape_xml(&written[..prefix].iter().collect::<String>()),
+        escape_xml(&base),
+        escape_xml(&rt),
+        escape_xml(&written[written.len() - suffix..].iter().collect::<String>())
+    )
+}
+
+fn marked_ruby_token(token: &Token, options: &RenderOptions) -> String {
+    if options.is_low_confidence(token) {
+        format!(
+            "<mark title=\"confidence {:.2}\">{}</mark>",
+            token.confidence,
+            ruby_token(token)
+        )
+    } else {
+        ruby_token(token)
+    }
+}
+
+pub fn to_ruby_html(transcript: &Transcript, options: &RenderOptions) -> String {
+    let mut s = String::from(
+        "<!DOCTYPE html>\n<html lang=\"ja\">\n<head>\n<meta charset=\"utf-8\">\n</head>\n<body>\n",
+    );
+    for utterance in &transcript.utterances {
+        let line: String = utterance
+            .tokens
+            .iter()
+            .map(|token| marked_ruby_token(token, options))
+            .collect();
+        s.push_str(&format!("<p>{}</p>\n", line));
+    }
+    s.push_str("</body>\n</html>\n");
+    s
+}
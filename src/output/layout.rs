@@ -0,0 +1,434 @@
+use crate::output::RenderOptions;
+use crate::transcript::{join_decorated, Transcript, Utterance};
+use unicode_width::UnicodeWidthChar;
+
+#[derive(Debug, Clone, Default)]
+pub struct LayoutOptions {
+    pub max_line_chars: Option<f32>,
+    pub max_lines: Option<usize>,
+    pub min_duration: Option<u64>,
+    pub max_duration: Option<u64>,
+    pub max_chars_per_second: Option<f32>,
+}
+
+impl LayoutOptions {
+    fn is_unconstrained(&self) -> bool {
+        self.max_line_chars.is_none()
+            && self.max_lines.is_none()
+            && self.min_duration.is_none()
+            && self.max_duration.is_none()
+            && self.max_chars_per_second.is_none()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Cue {
+    pub starttime: u64,
+    pub endtime: u64,
+    pub lines: Vec<String>,
+    pub speaker: Option<String>,
+}
+
+impl Cue {
+    pub fn text(&self) -> String {
+        self.lines.join("\n")
+    }
+
+    fn char_count(&self) -> usize {
+        self.lines.iter().map(|line| line.chars().count()).sum()
+    }
+}
+
+pub fn display_width(s: &str) -> f32 {
+    s.chars()
+        .map(|c| match c.width() {
+            Some(2) => 1.0,
+            Some(0) => 0.0,
+            _ => 0.5,
+        })
+        .sum()
+}
+
+struct Piece {
+    // plain written form for spacing, empty for the continuation of a split token
+    written: String,
+    text: String,
+    starttime: u64,
+    endtime: u64,
+}
+
+fn join_pieces<'p, I>(pieces: I) -> String
+where
+    I: IntoIterator<Item = &'p Piece>,
+{
+    join_decorated(
+        pieces
+            .into_iter()
+            .map(|piece| (piece.written.as_str(), piece.text.as_str())),
+    )
+}
+
+fn split_by_width(text: &str, max_width: f32) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut chunk = String::new();
+    for c in text.chars() {
+        let mut next = chunk.clone();
+        next.push(c);
+        if !chunk.is_empty() && display_width(&next) > max_width {
+            chunks.push(chunk);
+            chunk = c.to_string();
+        } else {
+            chunk = next;
+        }
+    }
+    if !chunk.is_empty() {
+        chunks.push(chunk);
+    }
+    chunks
+}
+
+struct CueBuilder<'a> {
+    layout: &'a LayoutOptions,
+    speaker: Option<String>,
+    cues: Vec<Cue>,
+    lines: Vec<Vec<Piece>>,
+    starttime: u64,
+    endtime: u64,
+}
+
+impl<'a> CueBuilder<'a> {
+    fn line_width(&self) -> f32 {
+        self.lines
+            .last()
+            .map_or(0.0, |line| display_width(&join_pieces(line)))
+    }
+
+    fn flush(&mut self) {
+        if self.lines.is_empty() {
+            return;
+        }
+        let lines = self
+            .lines
+            .drain(..)
+            .map(|line| join_pieces(&line))
+            .collect();
+        self.cues.push(Cue {
+            starttime: self.starttime,
+            endtime: self.endtime,
+            lines,
+            speaker: self.speaker.clone(),
+        });
+    }
+
+    fn push(&mut self, piece: Piece) {
+        if !self.lines.is_empty() {
+            let is_too_long = matches!(
+                self.layout.max_duration,
+                Some(max) if piece.endtime.saturating_sub(self.starttime) > max
+            );
+            if is_too_long {
+                self.flush();
+            }
+        }
+
+        if self.lines.is_empty() {
+            self.starttime = piece.starttime;
+            self.lines.push(Vec::new());
+        } else if let Some(max_width) = self.layout.max_line_chars {
+            let line = self.lines.last().map_or(&[][..], |line| line.as_slice());
+            let width = display_width(&join_pieces(line.iter().chain(Some(&piece))));
+            if width > max_width && self.line_width() > 0.0 {
+                if matches!(self.layout.max_lines, Some(max) if self.lines.len() >= max) {
+                    self.flush();
+                    self.starttime = piece.starttime;
+                }
+                self.lines.push(Vec::new());
+            }
+        }
+
+        self.endtime = piece.endtime;
+        if let Some(line) = self.lines.last_mut() {
+            line.push(piece);
+        }
+    }
+}
+
+fn pieces_of(utterance: &Utterance, options: &RenderOptions) -> Vec<Piece> {
+    if utterance.tokens.is_empty() {
+        return vec![Piece {
+            written: utterance.text.clone(),
+            text: utterance.text.clone(),
+            starttime: utterance.starttime,
+            endtime: utterance.endtime,
+        }];
+    }
+    let mut pieces = Vec::new();
+    for token in &utterance.tokens {
+        let text = options.token_text(token);
+        let chunks = match options.layout.max_line_chars {
+            Some(max_width) if display_width(&text) > max_width => split_by_width(&text, max_width),
+            _ => vec![text],
+        };
+        // the time of a split token is shared by its chunks in proportion to their length
+        let total = chunks
+            .iter()
+            .map(|c| c.chars().count())
+            .sum::<usize>()
+            .max(1) as u64;
+        let duration = token.endtime.saturating_sub(token.starttime);
+        let mut count = 0;
+        for (index, chunk) in chunks.into_iter().enumerate() {
+            let starttime = token.starttime + duration * count / total;
+            count += chunk.chars().count() as u64;
+            pieces.push(Piece {
+                written: if index == 0 {
+                    token.written.clone()
+                } else {
+                    String::new()
+                },
+                text: chunk,
+                starttime,
+                endtime: token.starttime + duration * count / total,
+            });
+        }
+    }
+    pieces
+}
+
+// extends cues to the minimum and reading time and cuts them at the maximum.
+// returns the number of cues that are shorter than required, because the next cue starts
+// earlier or --max-cue-duration does not leave enough time for the text.
+fn adjust_timing(cues: &mut [Cue], layout: &LayoutOptions) -> usize {
+    let mut violations = 0;
+    for index in 0..cues.len() {
+        let limit = cues.get(index + 1).map(|next| next.starttime);
+        let cue = &mut cues[index];
+
+        let mut required = layout.min_duration.unwrap_or(0);
+        if let Some(cps) = layout.max_chars_per_second {
+            if cps > 0.0 {
+                required = required.max((cue.char_count() as f32 / cps * 1000.0).ceil() as u64);
+            }
+        }
+
+        let mut endtime = cue.endtime.max(cue.starttime.saturating_add(required));
+        if let Some(limit) = limit {
+            endtime = endtime.min(limit.max(cue.endtime));
+        }
+        // a single token can be longer than the maximum
+        if let Some(max) = layout.max_duration {
+            endtime = endtime.min(cue.starttime.saturating_add(max));
+        }
+        if endtime.saturating_sub(cue.starttime) < required {
+            violations += 1;
+        }
+        cue.endtime = endtime;
+    }
+    violations
+}
+
+pub fn layout(transcript: &Transcript, options: &RenderOptions) -> Vec<Cue> {
+    let layout = &options.layout;
+    let utterances = transcript.utterances.iter().filter(|u| !u.text.is_empty());
+    if layout.is_unconstrained() {
+        return utterances
+            .map(|utterance| Cue {
+                starttime: utterance.starttime,
+                endtime: utterance.endtime,
+                lines: vec![options.utterance_text(utterance)],
+                speaker: utterance.speaker.clone(),
+            })
+            .collect();
+    }
+
+    let mut cues = Vec::new();
+    for utterance in utterances {
+        let mut builder = CueBuilder {
+            layout,
+            speaker: utterance.speaker.clone(),
+            cues: Vec::new(),
+            lines: Vec::new(),
+            starttime: utterance.starttime,
+            endtime: utterance.endtime,
+        };
+        for piece in pieces_of(utterance, options) {
+            builder.push(piece);
+        }
+        builder.flush();
+        if let Some(first) = builder.cues.first_mut() {
+            first.starttime = first.starttime.min(utterance.starttime);
+        }
+        if let Some(last) = builder.cues.last_mut() {
+            last.endtime = last.endtime.max(utterance.endtime);
+        }
+        cues.extend(builder.cues);
+    }
+    let violations = adjust_timing(&mut cues, layout);
+    if violations > 0 {
+        eprintln!(
+            "warning: {} subtitle cues are shorter than --min-cue-duration or --max-cps require",
+            violations
+        );
+    }
+    cues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cleanup::CleanupOptions;
+    use crate::shaping::ShapingOptions;
+    use crate::transcript::Token;
+
+    fn render_options(layout: LayoutOptions) -> RenderOptions {
+        RenderOptions {
+            confidence_threshold: 0.0,
+            is_mark_low_confidence: false,
+            cleanup: CleanupOptions::default(),
+            shaping: ShapingOptions::default(),
+            layout,
+            chapter_gap: 0,
+            language: None,
+            engines: Vec::new(),
+        }
+    }
+
+    fn token(written: &str, starttime: u64, endtime: u64) -> Token {
+        Token {
+            written: written.to_string(),
+            spoken: String::new(),
+            confidence: 1.0,
+            starttime,
+            endtime,
+            speaker: None,
+        }
+    }
+
+    fn transcript(tokens: Vec<Token>) -> Transcript {
+        Transcript {
+            utterances: vec![Utterance::from_tokens(tokens)],
+            ..Transcript::default()
+        }
+    }
+
+    // consecutive tokens of 500 ms from 0
+    fn words(words: &[&str]) -> Transcript {
+        transcript(
+            words
+                .iter()
+                .enumerate()
+                .map(|(i, word)| token(word, i as u64 * 500, (i as u64 + 1) * 500))
+                .collect(),
+        )
+    }
+
+    fn cue(starttime: u64, endtime: u64, text: &str) -> Cue {
+        Cue {
+            starttime,
+            endtime,
+            lines: vec![text.to_string()],
+            speaker: None,
+        }
+    }
+
+    fn lines(cues: &[Cue]) -> Vec<Vec<&str>> {
+        cues.iter()
+            .map(|cue| cue.lines.iter().map(|line| line.as_str()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn display_width_counts_half_width_as_half() {
+        assert_eq!(display_width("あいう"), 3.0);
+        assert_eq!(display_width("abc"), 1.5);
+        assert_eq!(display_width("GPUを"), 2.5);
+        assert_eq!(display_width("ｶﾅ"), 1.0);
+        assert_eq!(display_width("e\u{301}"), 0.5);
+    }
+
+    #[test]
+    fn lines_break_by_display_width() {
+        let options = render_options(LayoutOptions {
+            max_line_chars: Some(4.0),
+            ..LayoutOptions::default()
+        });
+        let cues = layout(&words(&["今日", "は", "GPU", "を", "使う"]), &options);
+        assert_eq!(lines(&cues), vec![vec!["今日は", "GPUを", "使う"]]);
+    }
+
+    #[test]
+    fn lines_overflow_into_a_new_cue() {
+        let options = render_options(LayoutOptions {
+            max_line_chars: Some(4.0),
+            max_lines: Some(2),
+            ..LayoutOptions::default()
+        });
+        let cues = layout(&words(&["今日", "は", "GPU", "を", "使う"]), &options);
+        assert_eq!(lines(&cues), vec![vec!["今日は", "GPUを"], vec!["使う"]]);
+        assert_eq!((cues[0].starttime, cues[0].endtime), (0, 2000));
+        assert_eq!((cues[1].starttime, cues[1].endtime), (2000, 2500));
+    }
+
+    #[test]
+    fn long_tokens_are_split_by_width() {
+        let options = render_options(LayoutOptions {
+            max_line_chars: Some(2.0),
+            ..LayoutOptions::default()
+        });
+        let utterance = Utterance::from_tokens(vec![token("あいうえお", 0, 1000)]);
+        let pieces = pieces_of(&utterance, &options);
+        let pieces: Vec<(&str, &str, u64, u64)> = pieces
+            .iter()
+            .map(|p| (p.written.as_str(), p.text.as_str(), p.starttime, p.endtime))
+            .collect();
+        assert_eq!(
+            pieces,
+            vec![
+                ("あいうえお", "あい", 0, 400),
+                ("", "うえ", 400, 800),
+                ("", "お", 800, 1000),
+            ]
+        );
+    }
+
+    #[test]
+    fn cues_end_at_max_duration() {
+        let options = render_options(LayoutOptions {
+            max_duration: Some(1000),
+            ..LayoutOptions::default()
+        });
+        let transcript = transcript(vec![
+            token("はい", 0, 500),
+            token("えええ", 500, 3000),
+            token("そう", 3000, 3500),
+        ]);
+        let cues = layout(&transcript, &options);
+        assert_eq!(
+            lines(&cues),
+            vec![vec!["はい"], vec!["えええ"], vec!["そう"]]
+        );
+        // the word longer than the maximum is shown for the maximum
+        assert_eq!((cues[1].starttime, cues[1].endtime), (500, 1500));
+        assert_eq!((cues[2].starttime, cues[2].endtime), (3000, 3500));
+    }
+
+    #[test]
+    fn reading_time_is_limited_by_the_next_cue() {
+        let layout = LayoutOptions {
+            min_duration: Some(1000),
+            max_chars_per_second: Some(5.0),
+            ..LayoutOptions::default()
+        };
+        let mut cues = vec![
+            cue(0, 500, "あいうえおかきくけこ"),
+            cue(1500, 2000, "はい"),
+            cue(3000, 3100, "そう"),
+        ];
+        assert_eq!(adjust_timing(&mut cues, &layout), 1);
+        // 10 characters need 2 seconds, but the next cue starts at 1.5 seconds
+        assert_eq!(cues[0].endtime, 1500);
+        // the minimum duration is longer than the reading time of 2 characters
+        assert_eq!(cues[1].endtime, 2500);
+        assert_eq!(cues[2].endtime, 4000);
+    }
+}
@@ -0,0 +1,354 @@
+mod annotation;
+mod ass;
+mod chapters;
+mod kana;
+pub mod layout;
+mod review;
+mod subtitle;
+mod ttml;
+mod words;
+
+use crate::ami::JsonOutput;
+use crate::cleanup::CleanupOptions;
+use crate::engines::Engine;
+use crate::shaping::ShapingOptions;
+use crate::transcript::{join_decorated, Token, Transcript, Utterance};
+use layout::LayoutOptions;
+use std::io::Write;
+use std::str::FromStr;
+
+pub const STDOUT_PATH: &str = "-";
+const DEFAULT_LANGUAGE: &str = "ja";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    Text,
+    Json,
+    Jsonl,
+    Srt,
+    Vtt,
+    Words,
+    Reading,
+    Hiragana,
+    Ruby,
+    Review,
+    Ass,
+    Ttml,
+    TextGrid,
+    Eaf,
+    Audacity,
+    FfMetadata,
+    Youtube,
+}
+
+impl OutputFormat {
+    pub const NAMES: &'static [&'static str] = &[
+        "text",
+        "json",
+        "jsonl",
+        "srt",
+        "vtt",
+        "ass",
+        "ttml",
+        "words",
+        "reading",
+        "hiragana",
+        "ruby",
+        "review",
+        "textgrid",
+        "eaf",
+        "audacity",
+        "ffmetadata",
+        "youtube",
+    ];
+
+    // formats read as text end with a notice when the session was interrupted. json and jsonl
+    // carry the flag or the error, and in words, chapters, labels and annotations the notice
+    // would look like a result
+    fn has_incomplete_notice(&self) -> bool {
+        !matches!(
+            self,
+            OutputFormat::Json
+                | OutputFormat::Jsonl
+                | OutputFormat::Words
+                | OutputFormat::TextGrid
+                | OutputFormat::Eaf
+                | OutputFormat::Audacity
+                | OutputFormat::FfMetadata
+                | OutputFormat::Youtube
+        )
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" | "txt" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            "jsonl" => Ok(OutputFormat::Jsonl),
+            "srt" => Ok(OutputFormat::Srt),
+            "vtt" => Ok(OutputFormat::Vtt),
+            "words" => Ok(OutputFormat::Words),
+            "reading" | "katakana" => Ok(OutputFormat::Reading),
+            "hiragana" => Ok(OutputFormat::Hiragana),
+            "ruby" | "html" => Ok(OutputFormat::Ruby),
+            "review" => Ok(OutputFormat::Review),
+            "ass" | "ssa" => Ok(OutputFormat::Ass),
+            "ttml" | "imsc" => Ok(OutputFormat::Ttml),
+            "textgrid" | "praat" => Ok(OutputFormat::TextGrid),
+            "eaf" | "elan" => Ok(OutputFormat::Eaf),
+            "audacity" | "labels" => Ok(OutputFormat::Audacity),
+            "ffmetadata" | "chapters" => Ok(OutputFormat::FfMetadata),
+            "youtube" => Ok(OutputFormat::Youtube),
+            _ => Err(format!(
+                "unknown output format \"{}\". supported formats: {}",
+                s,
+                OutputFormat::NAMES.join(", ")
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RenderOptions {
+    pub confidence_threshold: f32,
+    pub is_mark_low_confidence: bool,
+    pub cleanup: CleanupOptions,
+    pub shaping: ShapingOptions,
+    pub layout: LayoutOptions,
+    pub chapter_gap: u64,
+    pub language: Option<String>,
+    // the engine catalog of the resolved settings, to find the language of a session
+    pub engines: Vec<Engine>,
+}
+
+impl RenderOptions {
+    pub fn is_low_confidence(&self, token: &Token) -> bool {
+        self.is_mark_low_confidence && token.confidence < self.confidence_threshold
+    }
+
+    pub fn utterance_text(&self, utterance: &Utterance) -> String {
+        if !utterance
+            .tokens
+            .iter()
+            .any(|token| self.is_low_confidence(token))
+        {
+            return utterance.text.clone();
+        }
+        let words: Vec<String> = utterance
+            .tokens
+            .iter()
+            .map(|token| self.token_text(token))
+            .collect();
+        join_decorated(
+            utterance
+                .tokens
+                .iter()
+                .zip(&words)
+                .map(|(token, word)| (token.written.as_str(), word.as_str())),
+        )
+    }
+
+    pub fn token_text(&self, token: &Token) -> String {
+        if self.is_low_confidence(token) {
+            format!("[?{}?]", token.written)
+        } else {
+            token.written.clone()
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct OutputSpec {
+    pub format: OutputFormat,
+    pub path: String,
+}
+
+impl OutputSpec {
+    pub fn new(format: OutputFormat, path: &str) -> OutputSpec {
+        OutputSpec {
+            format,
+            path: path.to_string(),
+        }
+    }
+
+    pub fn is_stdout(&self) -> bool {
+        self.path == STDOUT_PATH
+    }
+
+    pub fn check(&self) -> Result<(), String> {
+        if self.is_stdout() {
+            return Ok(());
+        }
+        std::fs::write(&self.path, "").map_err(|e| {
+            format!(
+                "failed to write result file {} (empty write for check): {}",
+                self.path, e
+            )
+        })
+    }
+
+    pub fn is_streaming(&self) -> bool {
+        self.format == OutputFormat::Jsonl
+    }
+
+    pub fn open_stream(&self) -> Result<Box<dyn Write + Send>, String> {
+        if self.is_stdout() {
+            return Ok(Box::new(std::io::stdout()));
+        }
+        std::fs::File::create(&self.path)
+            .map(|f| Box::new(f) as Box<dyn Write + Send>)
+            .map_err(|e| format!("failed to open result file {}: {}", self.path, e))
+    }
+
+    pub fn write(&self, text: &str) -> Result<(), String> {
+        if self.is_stdout() {
+            let stdout = std::io::stdout();
+            let mut lock = stdout.lock();
+            lock.write_all(text.as_bytes())
+                .and_then(|_| lock.flush())
+                .map_err(|e| format!("failed to write result to stdout: {}", e))
+        } else {
+            std::fs::write(&self.path, text)
+                .map_err(|e| format!("failed to write result file {}: {}", self.path, e))
+        }
+    }
+}
+
+impl FromStr for OutputSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let index = s.find(':').ok_or_else(|| {
+            format!(
+                "failed to parse output \"{}\": expected <format>:<path> (e.g. srt:result.srt, text:-)",
+                s
+            )
+        })?;
+        let format = s[..index].parse()?;
+        let path = &s[index + 1..];
+        if path.is_empty() {
+            return Err(format!("failed to parse output \"{}\": path is empty", s));
+        }
+        Ok(OutputSpec::new(format, path))
+    }
+}
+
+pub fn escape_xml(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+pub fn check_outputs(specs: &[OutputSpec]) -> Result<(), String> {
+    if specs.is_empty() {
+        return Err("no output is specified. use --output-file or --output".to_string());
+    }
+    if specs.iter().filter(|spec| spec.is_stdout()).count() > 1 {
+        return Err("only one output can be written to stdout".to_string());
+    }
+    specs.iter().try_for_each(OutputSpec::check)
+}
+
+pub fn format_clock(millis: u64) -> String {
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        millis / 3_600_000,
+        millis / 60_000 % 60,
+        millis / 1000 % 60,
+        millis % 1000
+    )
+}
+
+fn to_text(transcript: &Transcript, options: &RenderOptions) -> String {
+    transcript
+        .utterances
+        .iter()
+        .map(|utterance| match &utterance.speaker {
+            Some(speaker) => format!("{}: {}", speaker, options.utterance_text(utterance)),
+            None => options.utterance_text(utterance),
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+// --subtitle-language, or the language of the engine used for the session
+fn subtitle_language(output: &JsonOutput, options: &RenderOptions) -> String {
+    if let Some(language) = &options.language {
+        return language.clone();
+    }
+    output
+        .option()
+        .grammar_file_names
+        .split_whitespace()
+        .find_map(|name| {
+            options
+                .engines
+                .iter()
+                .find(|engine| engine.name == name && !engine.language.is_empty())
+        })
+        .map_or_else(
+            || DEFAULT_LANGUAGE.to_string(),
+            |engine| engine.language.clone(),
+        )
+}
+
+pub fn render(
+    format: OutputFormat,
+    output: &JsonOutput,
+    options: &RenderOptions,
+) -> Result<String, String> {
+    let mut transcript = output.transcript();
+    options.cleanup.apply(&mut transcript);
+    options.shaping.apply(&mut transcript);
+    if output.is_incomplete() && format.has_incomplete_notice() {
+        transcript.push_incomplete_notice();
+    }
+    match format {
+        OutputFormat::Text => Ok(to_text(&transcript, options)),
+        // the API key or token must not end up in result files
+        OutputFormat::Json => output.without_authorization().to_json(),
+        OutputFormat::Jsonl => output.without_authorization().to_jsonl(),
+        OutputFormat::Srt => Ok(subtitle::to_srt(&transcript, options)),
+        OutputFormat::Vtt => Ok(subtitle::to_vtt(&transcript, options)),
+        OutputFormat::Words => words::to_words_json(&transcript),
+        OutputFormat::Reading => Ok(kana::to_katakana_reading(&transcript)),
+        OutputFormat::Hiragana => Ok(kana::to_hiragana_reading(&transcript)),
+        OutputFormat::Ruby => Ok(kana::to_ruby_html(&transcript, options)),
+        OutputFormat::Review => Ok(review::to_review(&transcript, options)),
+        OutputFormat::Ass => Ok(ass::to_ass(&transcript, options)),
+        OutputFormat::Ttml => Ok(ttml::to_ttml(
+            &transcript,
+            options,
+            &subtitle_language(output, options),
+        )),
+        OutputFormat::TextGrid => Ok(annotation::to_textgrid(&transcript, options)),
+        OutputFormat::Eaf => Ok(annotation::to_eaf(&transcript, options)),
+        OutputFormat::Audacity => Ok(chapters::to_audacity_labels(&transcript, options)),
+        OutputFormat::FfMetadata => Ok(chapters::to_ffmetadata(&transcript, options)),
+        OutputFormat::Youtube => Ok(chapters::to_youtube_chapters(&transcript, options)),
+    }
+}
+
+pub fn write_outputs(
+    specs: &[OutputSpec],
+    output: &JsonOutput,
+    options: &RenderOptions,
+) -> Result<(), String> {
+    for spec in specs {
+        let text = render(spec.format, output, options)?;
+        spec.write(&text)?;
+    }
+    Ok(())
+}
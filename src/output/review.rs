@@ -0,0 +1,54 @@
+use crate::output::{format_clock, RenderOptions};
+use crate::transcript::Transcript;
+
+pub fn to_review(transcript: &Transcript, options: &RenderOptions) -> String {
+    let threshold = options.confidence_threshold;
+    let mut s = format!("low confidence report (threshold {:.2})\n", threshold);
+    let mut count_utterances = 0;
+    let mut count_words = 0;
+
+    for utterance in &transcript.utterances {
+        let words: Vec<_> = utterance
+            .tokens
+            .iter()
+            .filter(|token| token.confidence < threshold)
+            .collect();
+        if utterance.confidence >= threshold && words.is_empty() {
+            continue;
+        }
+
+        count_utterances += 1;
+        count_words += words.len();
+        let file = match utterance.position {
+            Some(position) => format!(
+                " ({} {})",
+                transcript.files[position.file].path,
+                format_clock(position.file_starttime)
+            ),
+            None => String::new(),
+        };
+        s.push_str(&format!(
+            "\n[{} - {}]{} {:.2} {}\n",
+            format_clock(utterance.starttime),
+            format_clock(utterance.endtime),
+            file,
+            utterance.confidence,
+            utterance.text
+        ));
+        for token in words {
+            s.push_str(&format!(
+                "    {} - {}  {:.2}  {}\n",
+                format_clock(token.starttime),
+                format_clock(token.endtime),
+                token.confidence,
+                token.written
+            ));
+        }
+    }
+
+    s.push_str(&format!(
+        "\n{} utterances, {} words below threshold\n",
+        count_utterances, count_words
+    ));
+    s
+}
@@ -0,0 +1,52 @@
+use crate::output::layout::layout;
+use crate::output::RenderOptions;
+use crate::transcript::Transcript;
+
+fn format_timestamp(millis: u64, separator: char) -> String {
+    format!(
+        "{:02}:{:02}:{:02}{}{:03}",
+        millis / 3_600_000,
+        millis / 60_000 % 60,
+        millis / 1000 % 60,
+        separator,
+        millis % 1000
+    )
+}
+
+pub fn to_srt(transcript: &Transcript, options: &RenderOptions) -> String {
+    let mut s = String::new();
+    for (index, cue) in layout(transcript, options).iter().enumerate() {
+        s.push_str(&format!(
+            "{}\n{} --> {}\n{}\n\n",
+            index + 1,
+            format_timestamp(cue.starttime, ','),
+            format_timestamp(cue.endtime, ','),
+            cue.text()
+        ));
+    }
+    s
+}
+
+// WebVTT cue text is markup, & < > must be escaped
+fn escape_vtt(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+pub fn to_vtt(transcript: &Transcript, options: &RenderOptions) -> String {
+    let mut s = String::from("WEBVTT\n\n");
+    for cue in layout(transcript, options) {
+        let text = match &cue.speaker {
+            Some(speaker) => format!("<v {}>{}", escape_vtt(speaker), escape_vtt(&cue.text())),
+            None => escape_vtt(&cue.text()),
+        };
+        s.push_str(&format!(
+            "{} --> {}\n{}\n\n",
+            format_timestamp(cue.starttime, '.'),
+            format_timestamp(cue.endtime, '.'),
+            text
+        ));
+    }
+    s
+}
@@ -0,0 +1,112 @@
+use crate::output::layout::layout;
+use crate::output::{escape_xml, format_clock, RenderOptions};
+use crate::transcript::Transcript;
+
+const COLORS: &[&str] = &[
+    "#FFFFFF", "#FFFF00", "#00FFFF", "#00FF00", "#FF80FF", "#FF8000",
+];
+
+// speakers differing only in characters that are replaced get numbered ids
+fn agent_ids(speakers: &[&String]) -> Vec<String> {
+    let mut ids: Vec<String> = Vec::new();
+    for speaker in speakers {
+        let id = agent_id(speaker);
+        let mut unique = id.clone();
+        let mut number = 2;
+        while ids.contains(&unique) {
+            unique = format!("{}_{}", id, number);
+            number += 1;
+        }
+        ids.push(unique);
+    }
+    ids
+}
+
+fn agent_id(speaker: &str) -> String {
+    let id: String = speaker
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("agent_{}", id)
+}
+
+pub fn to_ttml(transcript: &Transcript, options: &RenderOptions, language: &str) -> String {
+    let cues = layout(transcript, options);
+
+    let mut speakers: Vec<&String> = Vec::new();
+    for speaker in cues.iter().filter_map(|cue| cue.speaker.as_ref()) {
+        if !speakers.contains(&speaker) {
+            speakers.push(speaker);
+        }
+    }
+
+    let ids = agent_ids(&speakers);
+    let id_of = |speaker: &String| {
+        let index = speakers.iter().position(|s| *s == speaker).unwrap_or(0);
+        ids[index].as_str()
+    };
+
+    let mut s = format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<tt xmlns="http://www.w3.org/ns/ttml" xmlns:tts="http://www.w3.org/ns/ttml#styling" xmlns:ttm="http://www.w3.org/ns/ttml#metadata" xmlns:ttp="http://www.w3.org/ns/ttml#parameter" ttp:profile="http://www.w3.org/ns/ttml/profile/imsc1/text" ttp:timeBase="media" xml:lang="{}">
+  <head>
+"#,
+        escape_xml(language)
+    );
+    if !speakers.is_empty() {
+        s.push_str("    <metadata>\n");
+        for (speaker, id) in speakers.iter().zip(&ids) {
+            s.push_str(&format!(
+                "      <ttm:agent xml:id=\"{}\" type=\"person\"><ttm:name type=\"full\">{}</ttm:name></ttm:agent>\n",
+                id,
+                escape_xml(speaker)
+            ));
+        }
+        s.push_str("    </metadata>\n");
+    }
+    s.push_str("    <styling>\n");
+    s.push_str("      <style xml:id=\"s_default\" tts:color=\"#FFFFFF\" tts:backgroundColor=\"#00000080\" tts:fontSize=\"100%\" tts:textAlign=\"center\"/>\n");
+    for (index, id) in ids.iter().enumerate() {
+        s.push_str(&format!(
+            "      <style xml:id=\"s_{}\" style=\"s_default\" tts:color=\"{}\"/>\n",
+            id,
+            COLORS[index % COLORS.len()]
+        ));
+    }
+    s.push_str("    </styling>\n");
+    s.push_str("    <layout>\n      <region xml:id=\"bottom\" tts:origin=\"10% 75%\" tts:extent=\"80% 20%\" tts:displayAlign=\"after\"/>\n    </layout>\n");
+    s.push_str("  </head>\n  <body region=\"bottom\">\n    <div>\n");
+
+    for cue in &cues {
+        let text = cue
+            .lines
+            .iter()
+            .map(|line| escape_xml(line))
+            .collect::<Vec<String>>()
+            .join("<br/>");
+        let attributes = match &cue.speaker {
+            Some(speaker) => format!(
+                " style=\"s_{}\" ttm:agent=\"{}\"",
+                id_of(speaker),
+                id_of(speaker)
+            ),
+            None => " style=\"s_default\"".to_string(),
+        };
+        s.push_str(&format!(
+            "      <p begin=\"{}\" end=\"{}\"{}>{}</p>\n",
+            format_clock(cue.starttime),
+            format_clock(cue.endtime),
+            attributes,
+            text
+        ));
+    }
+
+    s.push_str("    </div>\n  </body>\n</tt>\n");
+    s
+}
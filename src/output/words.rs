@@ -0,0 +1,46 @@
+use crate::transcript::{FilePosition, Transcript};
+use serde::Serialize;
+
+#[derive(Debug, Serialize)]
+struct Word<'a> {
+    utterance: usize,
+    written: &'a str,
+    spoken: &'a str,
+    confidence: f32,
+    starttime: u64,
+    endtime: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    speaker: Option<&'a str>,
+    // source file of the utterance and the start time within it when several files are concatenated
+    #[serde(flatten, skip_serializing_if = "Option::is_none")]
+    position: Option<FilePosition>,
+}
+
+pub fn to_words_json(transcript: &Transcript) -> Result<String, String> {
+    let words: Vec<Word> = transcript
+        .utterances
+        .iter()
+        .enumerate()
+        .flat_map(|(index, utterance)| {
+            utterance.tokens.iter().map(move |token| {
+                let position = utterance.position.map(|position| FilePosition {
+                    file: position.file,
+                    file_starttime: token
+                        .starttime
+                        .saturating_sub(transcript.files[position.file].starttime),
+                });
+                Word {
+                    utterance: index,
+                    written: &token.written,
+                    spoken: &token.spoken,
+                    confidence: token.confidence,
+                    starttime: token.starttime,
+                    endtime: token.endtime,
+                    speaker: token.speaker.as_deref(),
+                    position,
+                }
+            })
+        })
+        .collect();
+    serde_json::to_string_pretty(&words).map_err(|e| format!("failed to serialize words: {}", e))
+}
@@ -0,0 +1,114 @@
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_CHUNK_SIZE: &str = "4096";
+
+// pause after every chunk of the fixed mode
+const FIXED_INTERVAL: Duration = Duration::from_millis(5);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PacingMode {
+    // a short fixed pause after every chunk
+    Fixed,
+    // as fast as the audio would play
+    Realtime,
+    // as fast as the server accepts
+    Throughput,
+}
+
+impl PacingMode {
+    pub const NAMES: &'static [&'static str] = &["fixed", "realtime", "throughput"];
+}
+
+impl FromStr for PacingMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "fixed" => Ok(PacingMode::Fixed),
+            "realtime" => Ok(PacingMode::Realtime),
+            "throughput" => Ok(PacingMode::Throughput),
+            _ => Err(format!("unknown pacing mode \"{}\"", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Pacing {
+    pub mode: PacingMode,
+    pub chunk_size: usize,
+    // audio bytes per second and the leading bytes that are not samples (WAV header), for realtime
+    pub bytes_per_second: u64,
+    pub header_size: u64,
+}
+
+impl Default for Pacing {
+    fn default() -> Self {
+        Pacing {
+            mode: PacingMode::Fixed,
+            chunk_size: parse_chunk_size(DEFAULT_CHUNK_SIZE).unwrap(),
+            bytes_per_second: 0,
+            header_size: 0,
+        }
+    }
+}
+
+pub fn parse_chunk_size(s: &str) -> Result<usize, String> {
+    match s.parse::<usize>() {
+        Ok(size) if size > 0 => Ok(size),
+        _ => Err(format!(
+            "invalid chunk size \"{}\": must be a positive number of bytes",
+            s
+        )),
+    }
+}
+
+// decides when the next chunk of audio may be sent
+pub struct Pacer {
+    pacing: Pacing,
+    started: Option<Instant>,
+    next: Option<Instant>,
+    sent: u64,
+}
+
+impl Pacer {
+    pub fn new(pacing: Pacing) -> Pacer {
+        Pacer {
+            pacing,
+            started: None,
+            next: None,
+            sent: 0,
+        }
+    }
+
+    // time left before the next chunk, None when it can be sent now
+    pub fn wait(&self) -> Option<Duration> {
+        let next = self.next?;
+        let now = Instant::now();
+        if next > now {
+            Some(next - now)
+        } else {
+            None
+        }
+    }
+
+    pub fn sent(&mut self, size: usize) {
+        let now = Instant::now();
+        let started = *self.started.get_or_insert(now);
+        self.sent += size as u64;
+        self.next = match self.pacing.mode {
+            PacingMode::Fixed => Some(now + FIXED_INTERVAL),
+            // scheduled from the start, so that waking up late does not accumulate
+            PacingMode::Realtime => {
+                let samples = self.sent.saturating_sub(self.pacing.header_size);
+                Some(
+                    started
+                        + Duration::from_micros(
+                            samples * 1_000_000 / self.pacing.bytes_per_second.max(1),
+                        ),
+                )
+            }
+            PacingMode::Throughput => None,
+        };
+    }
+}
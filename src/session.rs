@@ -0,0 +1,202 @@
+use crate::ami::{endpoint_url, AmiWebSocketClient, JsonOutput, OutputType, SCommandOption};
+use crate::audio::{self, TimeRange};
+use crate::cache;
+use crate::pacing::{Pacing, PacingMode};
+use crate::timeout::Timeouts;
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+// how sessions are run, independent of the recognition parameters
+#[derive(Debug, Clone, Default)]
+pub struct SessionOptions {
+    pub is_with_log: bool,
+    pub is_no_cache: bool,
+    pub pacing: Pacing,
+    pub timeouts: Timeouts,
+}
+
+// a jsonl output shared by the sessions of several channels
+#[derive(Clone)]
+struct SharedSink(Arc<Mutex<Box<dyn Write + Send>>>);
+
+impl Write for SharedSink {
+    // writes the whole buffer under the lock so that lines of channels are not mixed
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let mut sink = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        sink.write_all(buf)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).flush()
+    }
+}
+
+// a channel of a WAV file transcribed in its own session
+pub struct Channel {
+    index: usize,
+    label: String,
+    event_sinks: Vec<SharedSink>,
+}
+
+impl Channel {
+    // writes the events of a session that did not stream them, labeled with the channel
+    fn write_events(&self, output: &JsonOutput) -> Result<(), String> {
+        for line in output.event_lines(Some(&self.label))? {
+            let line = format!("{}\n", line);
+            for sink in &self.event_sinks {
+                let mut sink = sink.clone();
+                sink.write_all(line.as_bytes())
+                    .and_then(|_| sink.flush())
+                    .map_err(|e| format!("failed to write event: {}", e))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// runs one recognition session without progress output, using the cache unless is_no_cache.
+// an error during the session is kept in the returned output.
+pub fn run_session(
+    option: SCommandOption,
+    options: &SessionOptions,
+    audio_file_path: &str,
+    range: &TimeRange,
+    channel: Option<&Channel>,
+) -> Result<JsonOutput, String> {
+    let result = start_session(option, options, audio_file_path, range, channel);
+    // a channel that failed to start still reports its error to the jsonl outputs
+    if let (Err(e), Some(channel)) = (&result, channel) {
+        if let Err(write_error) = channel.write_events(&JsonOutput::from_error(e.clone())) {
+            eprintln!("{}", write_error);
+        }
+    }
+    result
+}
+
+fn start_session(
+    option: SCommandOption,
+    options: &SessionOptions,
+    audio_file_path: &str,
+    range: &TimeRange,
+    channel: Option<&Channel>,
+) -> Result<JsonOutput, String> {
+    let endpoint = endpoint_url(options.is_with_log);
+    let variant = match channel {
+        Some(channel) => format!("{}ch{}", range.cache_key(), channel.index),
+        None => range.cache_key(),
+    };
+    let key = if options.is_no_cache {
+        None
+    } else {
+        let key = cache::compute_key(&[audio_file_path.to_string()], &option, &endpoint, &variant)?;
+        if let Some(entry) = cache::load(&key)? {
+            if let Some(channel) = channel {
+                channel.write_events(&entry.output)?;
+            }
+            return Ok(entry.output);
+        }
+        Some(key)
+    };
+
+    let audio = match channel {
+        Some(channel) => audio::open_channel(audio_file_path, range, channel.index)?,
+        None => audio::open(audio_file_path, &option.audio_format, range)?,
+    };
+    let mut client = AmiWebSocketClient::new(
+        option,
+        options.is_with_log,
+        audio,
+        OutputType::Nil,
+        options.timeouts.clone(),
+    )?;
+    client.set_time_offset(range.start);
+    client.set_pacing(options.pacing.clone());
+    if let Some(channel) = channel {
+        client.set_channel_label(&channel.label);
+        for sink in &channel.event_sinks {
+            client.add_event_sink(Box::new(sink.clone()));
+        }
+    }
+    let _ = client.exec();
+    if let Some(key) = key {
+        if client.output().is_succeeded() {
+            cache::warn_on_error(cache::store(
+                &key,
+                audio_file_path,
+                &endpoint,
+                client.output(),
+            ));
+        }
+    }
+    Ok(client.output().clone())
+}
+
+// transcribes every channel of a WAV file in its own session concurrently.
+// the events of every channel are written to the event sinks as they arrive.
+pub fn run_channels(
+    option: SCommandOption,
+    options: &SessionOptions,
+    audio_file_path: &str,
+    range: &TimeRange,
+    labels: &[String],
+    event_sinks: Vec<Box<dyn Write + Send>>,
+) -> Result<JsonOutput, String> {
+    let (channels, audio_format) = audio::channel_layout(audio_file_path)?;
+    let labels: Vec<String> = if labels.is_empty() {
+        (1..=channels).map(|i| format!("ch{}", i)).collect()
+    } else if labels.len() == channels {
+        labels.to_vec()
+    } else {
+        return Err(format!(
+            "{} channel labels are given but {} has {} channels",
+            labels.len(),
+            audio_file_path,
+            channels
+        ));
+    };
+    let mut options = options.clone();
+    if options.pacing.mode == PacingMode::Realtime {
+        let (bytes_per_second, _) = audio::byte_rate(audio_file_path, &audio_format)?;
+        options.pacing.bytes_per_second = bytes_per_second;
+        options.pacing.header_size = 0;
+    }
+
+    let event_sinks: Vec<SharedSink> = event_sinks
+        .into_iter()
+        .map(|sink| SharedSink(Arc::new(Mutex::new(sink))))
+        .collect();
+    let handles: Vec<_> = labels
+        .iter()
+        .enumerate()
+        .map(|(index, label)| {
+            let option = SCommandOption {
+                audio_format: audio_format.clone(),
+                ..option.clone()
+            };
+            let options = options.clone();
+            let audio_file_path = audio_file_path.to_string();
+            let range = *range;
+            let channel = Channel {
+                index,
+                label: label.clone(),
+                event_sinks: event_sinks.clone(),
+            };
+            thread::spawn(move || {
+                run_session(option, &options, &audio_file_path, &range, Some(&channel))
+            })
+        })
+        .collect();
+    let outputs = labels
+        .into_iter()
+        .zip(handles)
+        .map(|(label, handle)| {
+            let result = handle
+                .join()
+                .unwrap_or_else(|_| Err("session thread panicked".to_string()));
+            (label, result)
+        })
+        .collect();
+    Ok(JsonOutput::from_channels(option, outputs))
+}
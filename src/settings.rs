@@ -0,0 +1,258 @@
+use crate::engines::Engine;
+use crate::fs::{load_custom_engines, load_global_settings, resolve_config_path};
+use clap::ArgMatches;
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+
+const PROJECT_CONFIG_TOML: &str = ".also-sprach-ami.toml";
+const PROJECT_CONFIG_JSON: &str = ".also-sprach-ami.json";
+
+#[derive(Debug, Clone)]
+pub enum Source {
+    Default,
+    Global(PathBuf),
+    Project(PathBuf),
+    CommandLine,
+}
+
+impl Display for Source {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Source::Default => write!(f, "default"),
+            Source::Global(path) => write!(f, "global config: {}", path.display()),
+            Source::Project(path) => write!(f, "project config: {}", path.display()),
+            Source::CommandLine => write!(f, "command line"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Layer {
+    pub source: Source,
+    pub values: BTreeMap<String, Vec<String>>,
+    pub engines: Vec<Engine>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Resolved {
+    pub values: Vec<String>,
+    pub source: Source,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Settings {
+    layers: Vec<Layer>,
+}
+
+impl Settings {
+    pub fn load() -> Result<Settings, String> {
+        let mut layers = Vec::new();
+        if let Some(layer) = load_project_layer()? {
+            layers.push(layer);
+        }
+        let global_path = resolve_config_path()?;
+        if let Some(values) = load_global_settings()? {
+            layers.push(Layer {
+                source: Source::Global(global_path),
+                values,
+                engines: load_custom_engines()?,
+            });
+        }
+        Ok(Settings { layers })
+    }
+
+    pub fn layers(&self) -> &Vec<Layer> {
+        &self.layers
+    }
+
+    // custom engines of every layer. an engine in a higher priority layer replaces one with the same name.
+    pub fn engines(&self) -> Vec<Engine> {
+        let mut engines: Vec<Engine> = Vec::new();
+        for engine in self.layers.iter().rev().flat_map(|layer| &layer.engines) {
+            match engines.iter_mut().find(|e| e.name == engine.name) {
+                Some(e) => *e = engine.clone(),
+                None => engines.push(engine.clone()),
+            }
+        }
+        engines
+    }
+
+    pub fn check_keys(&self, keys: &[(&str, Option<&str>)]) -> Result<(), String> {
+        for layer in &self.layers {
+            for key in layer.values.keys() {
+                if !keys.iter().any(|(k, _)| k == key) {
+                    let known: Vec<&str> = keys.iter().map(|(k, _)| *k).collect();
+                    return Err(format!(
+                        "unknown setting \"{}\" in {}. known settings: {}",
+                        key,
+                        layer.source,
+                        known.join(", ")
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn lookup(&self, name: &str) -> Option<Resolved> {
+        self.layers.iter().find_map(|layer| {
+            layer.values.get(name).map(|values| Resolved {
+                values: values.clone(),
+                source: layer.source.clone(),
+            })
+        })
+    }
+
+    pub fn resolve(&self, args: &ArgMatches, name: &str) -> Option<Resolved> {
+        if args.occurrences_of(name) > 0 {
+            let mut values: Vec<String> = args
+                .values_of(name)
+                .map(|values| values.map(|v| v.to_string()).collect())
+                .unwrap_or_default();
+            if values.is_empty() {
+                values.push("true".to_string());
+            }
+            return Some(Resolved {
+                values,
+                source: Source::CommandLine,
+            });
+        }
+        if let Some(resolved) = self.lookup(name) {
+            return Some(resolved);
+        }
+        args.values_of(name).map(|values| Resolved {
+            values: values.map(|v| v.to_string()).collect(),
+            source: Source::Default,
+        })
+    }
+
+    pub fn value_of(&self, args: &ArgMatches, name: &str) -> Option<String> {
+        self.resolve(args, name)
+            .and_then(|resolved| resolved.values.last().cloned())
+    }
+
+    pub fn values_of(&self, args: &ArgMatches, name: &str) -> Vec<String> {
+        self.resolve(args, name)
+            .map(|resolved| resolved.values)
+            .unwrap_or_default()
+    }
+
+    pub fn flag_of(&self, args: &ArgMatches, name: &str) -> Result<bool, String> {
+        match self.value_of(args, name) {
+            Some(v) => parse_bool(name, &v),
+            None => Ok(false),
+        }
+    }
+}
+
+pub fn parse_bool(name: &str, value: &str) -> Result<bool, String> {
+    match value {
+        "true" => Ok(true),
+        "false" => Ok(false),
+        _ => Err(format!(
+            "failed to parse setting \"{}\": expected true or false but got \"{}\"",
+            name, value
+        )),
+    }
+}
+
+fn find_project_config(start: &Path) -> Option<PathBuf> {
+    start.ancestors().find_map(|dir| {
+        [PROJECT_CONFIG_TOML, PROJECT_CONFIG_JSON]
+            .iter()
+            .map(|name| dir.join(name))
+            .find(|path| path.is_file())
+    })
+}
+
+fn load_project_layer() -> Result<Option<Layer>, String> {
+    let cwd =
+        std::env::current_dir().map_err(|e| format!("failed to get current directory: {}", e))?;
+    let path = if let Some(path) = find_project_config(&cwd) {
+        path
+    } else {
+        return Ok(None);
+    };
+
+    let text = std::fs::read_to_string(&path)
+        .map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+    let invalid_engines =
+        |e: String| format!("failed to parse engines in {}: {}", path.display(), e);
+    let (values, engines) = if path.extension().and_then(|ext| ext.to_str()) == Some("toml") {
+        let value: toml::Value = toml::from_str(&text)
+            .map_err(|e| format!("failed to parse {}: {}", path.display(), e))?;
+        let engines = match value.get("engines") {
+            Some(engines) => engines
+                .clone()
+                .try_into()
+                .map_err(|e: toml::de::Error| invalid_engines(e.to_string()))?,
+            None => Vec::new(),
+        };
+        let values = match value.get("transcribe") {
+            Some(toml::Value::Table(table)) => table
+                .iter()
+                .map(|(k, v)| (k.clone(), toml_to_strings(v)))
+                .collect(),
+            Some(_) => {
+                return Err(format!(
+                    "[transcribe] in {} must be a table",
+                    path.display()
+                ))
+            }
+            None => BTreeMap::new(),
+        };
+        (values, engines)
+    } else {
+        let value: serde_json::Value = serde_json::from_str(&text)
+            .map_err(|e| format!("failed to parse {}: {}", path.display(), e))?;
+        let engines = match value.get("engines") {
+            Some(engines) => serde_json::from_value(engines.clone())
+                .map_err(|e| invalid_engines(e.to_string()))?,
+            None => Vec::new(),
+        };
+        let values = match value.get("transcribe") {
+            Some(serde_json::Value::Object(map)) => json_object_to_values(map),
+            Some(_) => {
+                return Err(format!(
+                    "\"transcribe\" in {} must be an object",
+                    path.display()
+                ))
+            }
+            None => BTreeMap::new(),
+        };
+        (values, engines)
+    };
+
+    Ok(Some(Layer {
+        source: Source::Project(path),
+        values,
+        engines,
+    }))
+}
+
+pub fn json_object_to_values(
+    map: &serde_json::Map<String, serde_json::Value>,
+) -> BTreeMap<String, Vec<String>> {
+    map.iter()
+        .filter(|(_, v)| !v.is_null())
+        .map(|(k, v)| (k.clone(), json_to_strings(v)))
+        .collect()
+}
+
+fn json_to_strings(value: &serde_json::Value) -> Vec<String> {
+    match value {
+        serde_json::Value::String(s) => vec![s.clone()],
+        serde_json::Value::Array(values) => values.iter().flat_map(json_to_strings).collect(),
+        serde_json::Value::Null => Vec::new(),
+        v => vec![v.to_string()],
+    }
+}
+
+fn toml_to_strings(value: &toml::Value) -> Vec<String> {
+    match value {
+        toml::Value::String(s) => vec![s.clone()],
+        toml::Value::Array(values) => values.iter().flat_map(toml_to_strings).collect(),
+        v => vec![v.to_string()],
+    }
+}
@@ -0,0 +1,207 @@
+use crate::transcript::{join_tokens, Token, Transcript, Utterance};
+
+#[derive(Debug, Clone, Default)]
+pub struct ShapingOptions {
+    pub paragraph_gap: Option<u64>,
+    pub max_chars: Option<usize>,
+}
+
+fn is_sentence_end(token: &Token) -> bool {
+    matches!(
+        token.written.chars().last(),
+        Some('。') | Some('．') | Some('.') | Some('？') | Some('?') | Some('！') | Some('!')
+    )
+}
+
+fn is_clause_end(token: &Token) -> bool {
+    is_sentence_end(token)
+        || matches!(
+            token.written.chars().last(),
+            Some('、') | Some('，') | Some(',')
+        )
+}
+
+impl ShapingOptions {
+    pub fn apply(&self, transcript: &mut Transcript) {
+        if let Some(gap) = self.paragraph_gap {
+            transcript.utterances = merge_utterances(&transcript.utterances, gap);
+        }
+        if let Some(max_chars) = self.max_chars {
+            transcript.utterances = transcript
+                .utterances
+                .iter()
+                .flat_map(|utterance| split_utterance(utterance, max_chars))
+                .collect();
+        }
+        transcript.locate_files();
+    }
+}
+
+fn merge_utterances(utterances: &[Utterance], gap: u64) -> Vec<Utterance> {
+    let mut merged: Vec<Utterance> = Vec::new();
+    for utterance in utterances.iter().filter(|u| !u.text.is_empty()) {
+        if let Some(last) = merged.last_mut() {
+            let is_close = utterance.starttime <= last.endtime.saturating_add(gap);
+            if is_close && utterance.speaker == last.speaker {
+                let duration_last = last.endtime.saturating_sub(last.starttime) as f32;
+                let duration_next = utterance.endtime.saturating_sub(utterance.starttime) as f32;
+                if duration_last + duration_next > 0.0 {
+                    last.confidence = (last.confidence * duration_last
+                        + utterance.confidence * duration_next)
+                        / (duration_last + duration_next);
+                }
+                last.text = join_tokens(vec![last.text.as_str(), utterance.text.as_str()]);
+                last.endtime = last.endtime.max(utterance.endtime);
+                last.tokens.extend(utterance.tokens.iter().cloned());
+                continue;
+            }
+        }
+        merged.push(utterance.clone());
+    }
+    merged
+}
+
+fn split_utterance(utterance: &Utterance, max_chars: usize) -> Vec<Utterance> {
+    if utterance.char_count() <= max_chars || utterance.tokens.len() < 2 {
+        return vec![utterance.clone()];
+    }
+
+    let mut pieces = Vec::new();
+    let mut rest: &[Token] = &utterance.tokens;
+    while !rest.is_empty() {
+        let mut count = 0;
+        let mut fit = 0;
+        for token in rest {
+            let len = token.written.chars().count();
+            if fit > 0 && count + len > max_chars {
+                break;
+            }
+            count += len;
+            fit += 1;
+        }
+        if fit == rest.len() {
+            pieces.push(rest.to_vec());
+            break;
+        }
+
+        let min = (fit / 2).max(1);
+        let at = (min..=fit)
+            .rev()
+            .find(|&i| is_sentence_end(&rest[i - 1]))
+            .or_else(|| (min..=fit).rev().find(|&i| is_clause_end(&rest[i - 1])))
+            .unwrap_or(fit);
+        pieces.push(rest[..at].to_vec());
+        rest = &rest[at..];
+    }
+
+    pieces
+        .into_iter()
+        .map(|tokens| {
+            let mut piece = Utterance::from_tokens(tokens);
+            if piece.speaker.is_none() {
+                piece.speaker = utterance.speaker.clone();
+            }
+            piece
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // consecutive tokens of 100 ms from `starttime`
+    fn utterance(words: &[&str], starttime: u64, speaker: Option<&str>) -> Utterance {
+        let mut utterance = Utterance::from_tokens(
+            words
+                .iter()
+                .enumerate()
+                .map(|(i, word)| Token {
+                    written: word.to_string(),
+                    spoken: String::new(),
+                    confidence: 1.0,
+                    starttime: starttime + i as u64 * 100,
+                    endtime: starttime + (i as u64 + 1) * 100,
+                    speaker: None,
+                })
+                .collect(),
+        );
+        utterance.speaker = speaker.map(|s| s.to_string());
+        utterance
+    }
+
+    fn texts(utterances: &[Utterance]) -> Vec<&str> {
+        utterances.iter().map(|u| u.text.as_str()).collect()
+    }
+
+    #[test]
+    fn merge_joins_utterances_within_the_gap() {
+        // the first ends at 200, the second starts at 700
+        let utterances = vec![
+            utterance(&["はい", "。"], 0, None),
+            utterance(&["そう", "です"], 700, None),
+        ];
+        let merged = merge_utterances(&utterances, 500);
+        assert_eq!(texts(&merged), vec!["はい。そうです"]);
+        assert_eq!((merged[0].starttime, merged[0].endtime), (0, 900));
+        assert_eq!(merged[0].tokens.len(), 4);
+
+        assert_eq!(merge_utterances(&utterances, 499).len(), 2);
+        assert_eq!(merge_utterances(&utterances, u64::MAX).len(), 1);
+    }
+
+    #[test]
+    fn merge_keeps_speakers_apart() {
+        let utterances = vec![
+            utterance(&["はい"], 0, Some("A")),
+            utterance(&["そう"], 100, Some("B")),
+            utterance(&["です"], 200, Some("B")),
+        ];
+        let merged = merge_utterances(&utterances, 1000);
+        assert_eq!(texts(&merged), vec!["はい", "そうです"]);
+        assert_eq!(merged[1].speaker.as_deref(), Some("B"));
+    }
+
+    #[test]
+    fn split_prefers_sentence_ends() {
+        let source = utterance(
+            &["はい", "、", "そう", "です", "。", "また", "明日"],
+            0,
+            Some("A"),
+        );
+        let pieces = split_utterance(&source, 8);
+        assert_eq!(texts(&pieces), vec!["はい、そうです。", "また明日"]);
+        assert!(pieces.iter().all(|p| p.speaker.as_deref() == Some("A")));
+
+        let source = utterance(&["明日", "は", "、", "晴れ", "でしょう"], 0, None);
+        let pieces = split_utterance(&source, 6);
+        assert_eq!(texts(&pieces), vec!["明日は、", "晴れでしょう"]);
+    }
+
+    #[test]
+    fn split_falls_back_to_word_boundaries() {
+        let source = utterance(&["あいう", "えお", "かきく", "けこ"], 0, None);
+        let pieces = split_utterance(&source, 6);
+        assert_eq!(texts(&pieces), vec!["あいうえお", "かきくけこ"]);
+
+        // a single token longer than the limit is kept as it is
+        let source = utterance(&["あいうえおかき"], 0, None);
+        assert_eq!(split_utterance(&source, 6).len(), 1);
+    }
+
+    #[test]
+    fn split_keeps_token_times() {
+        let source = utterance(&["あいう", "えお", "かきく", "けこ"], 1000, None);
+        let pieces = split_utterance(&source, 6);
+        let tokens: Vec<&Token> = pieces.iter().flat_map(|p| p.tokens.iter()).collect();
+        assert_eq!(tokens.len(), source.tokens.len());
+        for (piece, original) in tokens.iter().zip(&source.tokens) {
+            assert_eq!(
+                (piece.starttime, piece.endtime),
+                (original.starttime, original.endtime)
+            );
+        }
+        assert_eq!((pieces[0].starttime, pieces[0].endtime), (1000, 1200));
+        assert_eq!((pieces[1].starttime, pieces[1].endtime), (1200, 1400));
+    }
+}
@@ -0,0 +1,143 @@
+use std::sync::atomic::{AtomicI32, AtomicUsize, Ordering};
+
+static INTERRUPTS: AtomicUsize = AtomicUsize::new(0);
+// the first signal received, 0 before any
+static SIGNAL: AtomicI32 = AtomicI32::new(0);
+
+// keeps the handlers installed while a session runs
+pub struct Guard;
+
+struct Installed {
+    sessions: usize,
+    #[cfg(unix)]
+    previous: Vec<(libc::c_int, libc::sigaction)>,
+}
+
+static INSTALLED: std::sync::Mutex<Installed> = std::sync::Mutex::new(Installed {
+    sessions: 0,
+    #[cfg(unix)]
+    previous: Vec::new(),
+});
+
+// records the first signal. the second one exits at once
+fn on_signal(signal: i32) {
+    let _ = SIGNAL.compare_exchange(0, signal, Ordering::SeqCst, Ordering::SeqCst);
+    if INTERRUPTS.fetch_add(1, Ordering::SeqCst) > 0 {
+        exit_now(128 + signal);
+    }
+}
+
+#[cfg(unix)]
+fn exit_now(code: i32) {
+    unsafe { libc::_exit(code) };
+}
+
+#[cfg(not(unix))]
+fn exit_now(code: i32) {
+    std::process::exit(code);
+}
+
+#[cfg(unix)]
+fn install_handlers(installed: &mut Installed) -> Result<(), String> {
+    extern "C" fn handle(signal: libc::c_int) {
+        on_signal(signal);
+    }
+
+    for signal in &[libc::SIGINT, libc::SIGTERM] {
+        // without SA_RESTART, so that poll returns when a signal arrives
+        let mut action: libc::sigaction = unsafe { std::mem::zeroed() };
+        let mut previous: libc::sigaction = unsafe { std::mem::zeroed() };
+        action.sa_sigaction = handle as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        if unsafe { libc::sigemptyset(&mut action.sa_mask) } != 0
+            || unsafe { libc::sigaction(*signal, &action, &mut previous) } != 0
+        {
+            let e = std::io::Error::last_os_error();
+            remove_handlers(installed);
+            return Err(format!("failed to install signal handler: {}", e));
+        }
+        installed.previous.push((*signal, previous));
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn remove_handlers(installed: &mut Installed) {
+    for (signal, action) in installed.previous.drain(..) {
+        unsafe { libc::sigaction(signal, &action, std::ptr::null_mut()) };
+    }
+}
+
+// Ctrl-C and Ctrl-Break are reported with the numbers of SIGINT and SIGBREAK
+#[cfg(windows)]
+const CONSOLE_SIGNALS: &[(u32, i32)] = &[
+    (winapi::um::wincon::CTRL_C_EVENT, 2),
+    (winapi::um::wincon::CTRL_BREAK_EVENT, 21),
+];
+
+// the handler is called on its own thread. returning TRUE keeps the process running
+#[cfg(windows)]
+unsafe extern "system" fn handle_console(event: u32) -> i32 {
+    match CONSOLE_SIGNALS.iter().find(|(e, _)| *e == event) {
+        Some((_, signal)) => {
+            on_signal(*signal);
+            1
+        }
+        None => 0,
+    }
+}
+
+#[cfg(windows)]
+fn install_handlers(_installed: &mut Installed) -> Result<(), String> {
+    if unsafe { winapi::um::consoleapi::SetConsoleCtrlHandler(Some(handle_console), 1) } == 0 {
+        let e = std::io::Error::last_os_error();
+        return Err(format!("failed to install console control handler: {}", e));
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
+fn remove_handlers(_installed: &mut Installed) {
+    unsafe { winapi::um::consoleapi::SetConsoleCtrlHandler(Some(handle_console), 0) };
+}
+
+#[cfg(not(any(unix, windows)))]
+fn install_handlers(_installed: &mut Installed) -> Result<(), String> {
+    Ok(())
+}
+
+#[cfg(not(any(unix, windows)))]
+fn remove_handlers(_installed: &mut Installed) {}
+
+// after the first SIGINT/SIGTERM (Ctrl-C/Ctrl-Break on Windows) sessions stop sending audio and
+// finish with the results so far. the second one exits at once. concurrent sessions share the
+// handlers, the previous ones are restored when the last session drops its guard.
+pub fn install() -> Result<Guard, String> {
+    let mut installed = INSTALLED.lock().unwrap_or_else(|e| e.into_inner());
+    if installed.sessions == 0 {
+        install_handlers(&mut installed)?;
+    }
+    installed.sessions += 1;
+    Ok(Guard)
+}
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        let mut installed = INSTALLED.lock().unwrap_or_else(|e| e.into_inner());
+        installed.sessions -= 1;
+        if installed.sessions == 0 {
+            remove_handlers(&mut installed);
+        }
+    }
+}
+
+pub fn is_interrupted() -> bool {
+    INTERRUPTS.load(Ordering::SeqCst) > 0
+}
+
+// 128 + the signal number, as shells report a process killed by the signal
+pub fn exit_code() -> Option<i32> {
+    match SIGNAL.load(Ordering::SeqCst) {
+        0 => None,
+        signal => Some(128 + signal),
+    }
+}
@@ -0,0 +1,46 @@
+use crate::audio::parse_time;
+use std::time::Duration;
+
+pub const DEFAULT_CONNECT_TIMEOUT: &str = "10";
+pub const DEFAULT_INIT_TIMEOUT: &str = "30";
+pub const DEFAULT_IDLE_TIMEOUT: &str = "60";
+pub const DEFAULT_SESSION_TIMEOUT: &str = "0";
+
+// None disables the timeout
+#[derive(Debug, Clone)]
+pub struct Timeouts {
+    // TCP connection, TLS and websocket handshakes
+    pub connect: Option<Duration>,
+    // from the s command to its response
+    pub initialize: Option<Duration>,
+    // without any event from the server or audio accepted by it, after initialization
+    pub idle: Option<Duration>,
+    // the whole session after connecting
+    pub session: Option<Duration>,
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        let parse = |s| parse_timeout("", s).unwrap();
+        Timeouts {
+            connect: parse(DEFAULT_CONNECT_TIMEOUT),
+            initialize: parse(DEFAULT_INIT_TIMEOUT),
+            idle: parse(DEFAULT_IDLE_TIMEOUT),
+            session: parse(DEFAULT_SESSION_TIMEOUT),
+        }
+    }
+}
+
+// seconds or clock time like --start. 0 disables the timeout
+pub fn parse_timeout(name: &str, s: &str) -> Result<Option<Duration>, String> {
+    let millis = parse_time(s).map_err(|e| format!("failed to parse --{}: {}", name, e))?;
+    if millis == 0 {
+        Ok(None)
+    } else {
+        Ok(Some(Duration::from_millis(millis)))
+    }
+}
+
+pub fn format_duration(duration: Duration) -> String {
+    format!("{}s", duration.as_secs_f64())
+}
@@ -0,0 +1,222 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Token {
+    pub written: String,
+    pub spoken: String,
+    pub confidence: f32,
+    pub starttime: u64,
+    pub endtime: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub speaker: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Utterance {
+    pub text: String,
+    pub confidence: f32,
+    pub starttime: u64,
+    pub endtime: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub speaker: Option<String>,
+    // where the utterance starts when several files are concatenated
+    #[serde(default, flatten, skip_serializing_if = "Option::is_none")]
+    pub position: Option<FilePosition>,
+    pub tokens: Vec<Token>,
+}
+
+impl Utterance {
+    pub fn from_tokens(tokens: Vec<Token>) -> Utterance {
+        let duration: u64 = tokens
+            .iter()
+            .map(|t| t.endtime.saturating_sub(t.starttime))
+            .sum();
+        let confidence = if tokens.is_empty() {
+            0.0
+        } else if duration == 0 {
+            tokens.iter().map(|t| t.confidence).sum::<f32>() / tokens.len() as f32
+        } else {
+            tokens
+                .iter()
+                .map(|t| t.confidence * t.endtime.saturating_sub(t.starttime) as f32)
+                .sum::<f32>()
+                / duration as f32
+        };
+        Utterance {
+            text: join_tokens(tokens.iter().map(|t| t.written.as_str())),
+            confidence,
+            // tokens are not always in order of time
+            starttime: tokens.iter().map(|t| t.starttime).min().unwrap_or(0),
+            endtime: tokens.iter().map(|t| t.endtime).max().unwrap_or(0),
+            speaker: major_speaker(&tokens),
+            position: None,
+            tokens,
+        }
+    }
+
+    pub fn char_count(&self) -> usize {
+        self.text.chars().count()
+    }
+}
+
+pub fn major_speaker(tokens: &[Token]) -> Option<String> {
+    let mut counts: Vec<(&String, usize)> = Vec::new();
+    for speaker in tokens.iter().filter_map(|t| t.speaker.as_ref()) {
+        match counts.iter_mut().find(|(s, _)| *s == speaker) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((speaker, 1)),
+        }
+    }
+    counts
+        .into_iter()
+        .fold(None, |best: Option<(&String, usize)>, current| match best {
+            Some(best) if best.1 >= current.1 => Some(best),
+            _ => Some(current),
+        })
+        .map(|(speaker, _)| speaker.clone())
+}
+
+// one of the audio files streamed back to back in a session and its time span in the session
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SourceFile {
+    pub path: String,
+    pub starttime: u64,
+    pub endtime: u64,
+}
+
+// last utterance of an interrupted session in outputs that cannot carry is_incomplete
+pub const INCOMPLETE_NOTICE: &str = "[interrupted: the results after this point are missing]";
+const INCOMPLETE_NOTICE_DURATION: u64 = 2000;
+
+// index of a source file and a time within that file
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub struct FilePosition {
+    pub file: usize,
+    pub file_starttime: u64,
+}
+
+// the source file containing the time of the session
+pub fn file_position(files: &[SourceFile], time: u64) -> Option<FilePosition> {
+    let index = files.iter().rposition(|file| file.starttime <= time)?;
+    Some(FilePosition {
+        file: index,
+        file_starttime: time - files[index].starttime,
+    })
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct Transcript {
+    pub utterances: Vec<Utterance>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub files: Vec<SourceFile>,
+}
+
+impl Transcript {
+    // sets the source file position of every utterance, after utterances are built or reshaped
+    pub fn locate_files(&mut self) {
+        for utterance in self.utterances.iter_mut() {
+            utterance.position = file_position(&self.files, utterance.starttime);
+        }
+    }
+
+    pub fn push_incomplete_notice(&mut self) {
+        let starttime = self.utterances.iter().map(|u| u.endtime).max().unwrap_or(0);
+        let mut notice = Utterance::from_tokens(vec![Token {
+            written: INCOMPLETE_NOTICE.to_string(),
+            spoken: INCOMPLETE_NOTICE.to_string(),
+            confidence: 1.0,
+            starttime,
+            endtime: starttime + INCOMPLETE_NOTICE_DURATION,
+            speaker: None,
+        }]);
+        notice.position = file_position(&self.files, starttime);
+        self.utterances.push(notice);
+    }
+}
+
+fn needs_space(prev: &str, next: &str) -> bool {
+    let is_word_char = |c: Option<char>| matches!(c, Some(c) if c.is_ascii_alphanumeric());
+    is_word_char(prev.chars().last()) && is_word_char(next.chars().next())
+}
+
+pub fn join_tokens<'a, I>(tokens: I) -> String
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    join_decorated(tokens.into_iter().map(|token| (token, token)))
+}
+
+// joins decorated tokens (e.g. [?word?]) with the spacing of their plain written forms
+pub fn join_decorated<'a, 'b, I>(tokens: I) -> String
+where
+    I: IntoIterator<Item = (&'a str, &'b str)>,
+{
+    let mut s = String::new();
+    let mut last = "";
+    for (written, decorated) in tokens {
+        if needs_space(last, written) {
+            s.push(' ');
+        }
+        s.push_str(decorated);
+        if !written.is_empty() {
+            last = written;
+        }
+    }
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn join_decorated_spaces_by_plain_tokens() {
+        let tokens = vec![("Hello", "[?Hello?]"), ("world", "world"), ("です", "です")];
+        assert_eq!(join_decorated(tokens), "[?Hello?] worldです");
+        assert_eq!(
+            join_tokens(vec!["AI", "を", "使う", "GPU", "2"]),
+            "AIを使うGPU 2"
+        );
+    }
+
+    #[test]
+    fn incomplete_notice_follows_the_last_utterance() {
+        let token = |written: &str, starttime, endtime| Token {
+            written: written.to_string(),
+            spoken: String::new(),
+            confidence: 0.9,
+            starttime,
+            endtime,
+            speaker: None,
+        };
+        let mut transcript = Transcript {
+            utterances: vec![
+                Utterance::from_tokens(vec![token("a", 0, 5000)]),
+                Utterance::from_tokens(vec![token("b", 3000, 4000)]),
+            ],
+            files: vec![
+                SourceFile {
+                    path: "1.wav".to_string(),
+                    starttime: 0,
+                    endtime: 4000,
+                },
+                SourceFile {
+                    path: "2.wav".to_string(),
+                    starttime: 4000,
+                    endtime: 6000,
+                },
+            ],
+        };
+        transcript.push_incomplete_notice();
+        let notice = transcript.utterances.last().unwrap();
+        assert_eq!(notice.text, INCOMPLETE_NOTICE);
+        assert_eq!((notice.starttime, notice.endtime), (5000, 7000));
+        assert_eq!(
+            notice.position,
+            Some(FilePosition {
+                file: 1,
+                file_starttime: 1000
+            })
+        );
+    }
+}